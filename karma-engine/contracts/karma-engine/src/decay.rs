@@ -0,0 +1,33 @@
+// Fixed-point scale for the fractional part of a half-life
+const SCALE: i128 = 1_000_000;
+
+// 2^-f on [0, 1] approximated by 1 - A*f + B*f^2, exact at f = 0, 1/2 and 1
+const A: i128 = 671_573;
+const B: i128 = 171_573;
+
+/// Decay `karma` towards zero after `elapsed` seconds with the given half-life.
+/// A half-life of 0 disables decay.
+pub fn decay(karma: i32, elapsed: u64, half_life: u64) -> i32 {
+    if half_life == 0 || elapsed == 0 || karma == 0 {
+        return karma;
+    }
+
+    let halvings = elapsed / half_life;
+    if halvings >= 32 {
+        return 0;
+    }
+
+    // Whole half-lives are exact shifts of the magnitude
+    let magnitude = (karma.unsigned_abs() >> halvings) as i128;
+
+    // The remainder is interpolated along the curve
+    let f = ((elapsed % half_life) as i128) * SCALE / (half_life as i128);
+    let factor = SCALE - A * f / SCALE + B * f * f / SCALE / SCALE;
+    let decayed = (magnitude * factor / SCALE) as i32;
+
+    if karma < 0 {
+        -decayed
+    } else {
+        decayed
+    }
+}
@@ -0,0 +1,90 @@
+use soroban_sdk::{Address, Env, Vec};
+
+use crate::storage;
+use crate::{LeaderboardEntry, UserData};
+
+/// Move `user` to its place on the leaderboard after a karma change.
+///
+/// Every entry decays at the same rate, so comparing decayed values at the
+/// current ledger time keeps the stored order valid without touching anyone
+/// else. Users outside the board only enter it on their next karma update.
+pub fn update(e: &Env, user: &Address, user_data: &UserData) {
+    let size = storage::read_leaderboard_size(e);
+    let mut leaderboard = storage::read_leaderboard(e);
+    let before = leaderboard.clone();
+
+    if let Some(index) = position(&leaderboard, user) {
+        leaderboard.remove(index);
+    }
+
+    if user_data.karma_points > 0 {
+        // Find the first entry this user outranks
+        let mut index = leaderboard.len();
+        for (i, entry) in leaderboard.iter().enumerate() {
+            if user_data.karma_points > storage::decayed(e, entry.karma, entry.updated_at) {
+                index = i as u32;
+                break;
+            }
+        }
+
+        if index < size {
+            leaderboard.insert(
+                index,
+                LeaderboardEntry {
+                    user: user.clone(),
+                    karma: user_data.karma_points,
+                    updated_at: user_data.last_update,
+                },
+            );
+        }
+    }
+
+    while leaderboard.len() > size {
+        leaderboard.pop_back();
+    }
+
+    if leaderboard != before {
+        storage::write_leaderboard(e, &leaderboard);
+    }
+}
+
+/// Shrink or grow the number of places kept on the leaderboard
+pub fn resize(e: &Env, size: u32) {
+    storage::write_leaderboard_size(e, size);
+
+    let mut leaderboard = storage::read_leaderboard(e);
+    if leaderboard.len() > size {
+        while leaderboard.len() > size {
+            leaderboard.pop_back();
+        }
+        storage::write_leaderboard(e, &leaderboard);
+    }
+}
+
+/// Entries `offset..offset + limit` with karma decayed to the current ledger time
+pub fn page(e: &Env, offset: u32, limit: u32) -> Vec<LeaderboardEntry> {
+    let leaderboard = storage::read_leaderboard(e);
+    let now = e.ledger().timestamp();
+
+    let mut result = Vec::new(e);
+    for entry in leaderboard.iter().skip(offset as usize).take(limit as usize) {
+        result.push_back(LeaderboardEntry {
+            karma: storage::decayed(e, entry.karma, entry.updated_at),
+            updated_at: now,
+            user: entry.user,
+        });
+    }
+    result
+}
+
+/// 1-based rank of `user`, if they are on the leaderboard
+pub fn rank(e: &Env, user: &Address) -> Option<u32> {
+    position(&storage::read_leaderboard(e), user).map(|index| index + 1)
+}
+
+fn position(leaderboard: &Vec<LeaderboardEntry>, user: &Address) -> Option<u32> {
+    leaderboard
+        .iter()
+        .position(|entry| entry.user == *user)
+        .map(|index| index as u32)
+}
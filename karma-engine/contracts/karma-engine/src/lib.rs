@@ -1,6 +1,21 @@
 #![no_std]
-use soroban_sdk::{contract, contractimpl, contractmeta, contracttype, contracterror, Address, Env, Map, Vec, Symbol, symbol_short};
+// Events keep using `Events::publish` so the topic layout indexers rely on stays stable
+#![allow(deprecated)]
+use soroban_sdk::{contract, contractclient, contractimpl, contractmeta, contracttype, contracterror, Address, BytesN, Env, Map, Vec, Symbol, symbol_short, vec};
 use soroban_sdk::token::TokenClient;
+use soroban_sdk::xdr::ToXdr;
+
+mod decay;
+mod leaderboard;
+mod multiplier;
+mod ratelimit;
+mod redemption;
+mod rewards;
+mod roles;
+mod storage;
+mod timelock;
+
+pub use storage::DataKey;
 
 // Metadata for the contract
 contractmeta!(
@@ -8,29 +23,47 @@ contractmeta!(
     val = "Karma Engine - A decentralized reputation protocol for tracking user karma points"
 );
 
-// Define constants for activity karma values
+// Default karma values for the built-in activity kinds (overridable via `set_activity_kind`)
 const POST_KARMA: i32 = 5;
 const COMMENT_KARMA: i32 = 3;
 const LIKE_KARMA: i32 = 1;
 const REPOST_KARMA: i32 = 2;
 const REPORT_PENALTY: i32 = -5;
 
-// Define constants for staking multipliers
+// Default multiplier schedule (overridable via `set_multiplier_schedule`)
 const REGULAR_MULTIPLIER: u32 = 10; // 1.0x (stored as 10 with 1 decimal place)
 const TRUSTED_MULTIPLIER: u32 = 15; // 1.5x (stored as 15 with 1 decimal place)
 const INFLUENCER_MULTIPLIER: u32 = 20; // 2x (stored as 20 with 1 decimal place)
 
-// Define thresholds for staking tiers
-const TRUSTED_TIER_THRESHOLD: i128 = 100;
-const INFLUENCER_TIER_THRESHOLD: i128 = 500;
+// Stake values are normalized to the XLM token's 7 decimals, weights are in basis points
+const STAKE_VALUE_DECIMALS: u32 = 7;
+const FULL_WEIGHT: u32 = 10_000;
+
+// Default tier thresholds, in stake value (7 decimals)
+const TRUSTED_TIER_THRESHOLD: i128 = 100 * 10_000_000; // 100 XLM
+const INFLUENCER_TIER_THRESHOLD: i128 = 500 * 10_000_000; // 500 XLM
+
+// Above the influencer tier every doubling of the stake adds 0.5x, up to 5x
+const DEFAULT_SCALING_STEP: u32 = 5;
+const MAX_MULTIPLIER: u32 = 50;
 
-// Conversion rate: 10 karma points = 1 XLM (represented as 10000000 stroops)
+// Conversion rate: 10 karma points = 1 XLM
 // XLM uses 7 decimals (1 XLM = 10^7 stroops)
-const DEFAULT_KARMA_TO_XLM_RATE: i128 = 10 * 10_000_000; // 10 karma = 1 XLM
+const DEFAULT_RATE_KARMA: u32 = 10;
+const DEFAULT_RATE_STROOPS: i128 = 10_000_000;
+
+// Default share of a stake slashed on a confirmed violation, in basis points
+const DEFAULT_SLASH_RATE: u32 = 1_000; // 10%
+const SLASH_RELIEF_KARMA: i32 = 1_000; // Karma at which a slash takes half the slash rate
 
 // Maximum activity history to store per user
 const MAX_ACTIVITY_HISTORY: u32 = 20;
 
+// Largest `record_batch` that always fits the default network limits. In the
+// worst case (author share on, users and authors staking) an entry writes 7
+// ledger entries on top of 3 shared ones, against a limit of 50.
+pub const MAX_BATCH_SIZE: u32 = 6;
+
 // Custom errors
 #[contracterror]
 #[derive(Copy, Clone, Debug, Eq, PartialEq, PartialOrd, Ord)]
@@ -43,6 +76,24 @@ pub enum KarmaError {
     ContractPaused = 5,
     InsufficientKarma = 6,
     InvalidAmount = 7,
+    AttestationExpired = 8,
+    InvalidNonce = 9,
+    UnknownActivity = 10,
+    ActivityDisabled = 11,
+    RateLimited = 12,
+    DuplicateActivity = 13,
+    MissingAuthor = 14,
+    SelfInteraction = 15,
+    StillUnbonding = 16,
+    UnsupportedToken = 17,
+    InvalidSchedule = 18,
+    NothingToSlash = 19,
+    TreasuryInsufficient = 20,
+    RedemptionLimited = 21,
+    KarmaTokenNotSet = 22,
+    Timelocked = 23,
+    ChangeNotReady = 24,
+    UnknownChange = 25,
 }
 
 // Activity types
@@ -54,6 +105,55 @@ pub enum ActivityType {
     Like,
     Repost,
     Report,
+    Custom(Symbol), // Kind defined in the on-chain activity catalogue
+    Slash,          // Stake slashed by a moderator, never recorded by reporters
+}
+
+impl ActivityType {
+    /// Map a catalogue symbol to an activity type
+    pub fn from_symbol(kind: Symbol) -> Self {
+        if kind == symbol_short!("post") {
+            ActivityType::Post
+        } else if kind == symbol_short!("comment") {
+            ActivityType::Comment
+        } else if kind == symbol_short!("like") {
+            ActivityType::Like
+        } else if kind == symbol_short!("repost") {
+            ActivityType::Repost
+        } else if kind == symbol_short!("report") {
+            ActivityType::Report
+        } else if kind == symbol_short!("slash") {
+            ActivityType::Slash
+        } else {
+            ActivityType::Custom(kind)
+        }
+    }
+
+    /// Symbol this activity type is keyed by in the activity catalogue
+    pub fn symbol(&self) -> Symbol {
+        match self {
+            ActivityType::Post => symbol_short!("post"),
+            ActivityType::Comment => symbol_short!("comment"),
+            ActivityType::Like => symbol_short!("like"),
+            ActivityType::Repost => symbol_short!("repost"),
+            ActivityType::Report => symbol_short!("report"),
+            ActivityType::Slash => symbol_short!("slash"),
+            ActivityType::Custom(kind) => kind.clone(),
+        }
+    }
+
+    /// Whether this activity interacts with someone else's content and so needs a target author
+    pub fn is_interaction(&self) -> bool {
+        matches!(self, ActivityType::Comment | ActivityType::Like | ActivityType::Repost)
+    }
+}
+
+// Activity catalogue entry
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ActivityKind {
+    pub base_karma: i32, // Positive for rewards, negative for penalties
+    pub enabled: bool,
 }
 
 // Event types
@@ -67,13 +167,32 @@ pub enum KarmaEvent {
 }
 
 // Storage keys
-const OWNER: Symbol = symbol_short!("OWNER");
-const USERS: Symbol = symbol_short!("USERS");
-const STAKES: Symbol = symbol_short!("STAKES");
-const ACTIVITY: Symbol = symbol_short!("ACTIVITY");
+const OWNER: Symbol = symbol_short!("OWNER"); // Removed once ownership is renounced
+const PENDOWNER: Symbol = symbol_short!("PENDOWNER"); // Proposed owner waiting to accept
 const KARMATOK: Symbol = symbol_short!("KARMATOK"); // Token contract for XLM
-const KARMART: Symbol = symbol_short!("KARMART"); // Conversion rate (shortened to 7 chars)
+const REPTOKEN: Symbol = symbol_short!("REPTOKEN"); // KARMA token minted for redeemed karma
+const KARMARATE: Symbol = symbol_short!("KARMARATE"); // Conversion rate, replaces the old i128 KARMART
 const PAUSED: Symbol = symbol_short!("PAUSED"); // Contract pause status
+const ATTESTOR: Symbol = symbol_short!("ATTESTOR"); // ed25519 key that signs attestations
+
+// Mint entry point of the KARMA token (contracts/karma-token), whose admin is this contract.
+// Only the generated client is used.
+#[contractclient(name = "KarmaTokenClient")]
+#[allow(dead_code)]
+trait KarmaTokenInterface {
+    fn mint(env: Env, to: Address, amount: i128);
+}
+
+// Permissions that can be granted to addresses; the owner holds all of them
+#[contracttype]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Role {
+    Admin,      // Contract settings, treasury and maintenance
+    Pauser,     // Pausing and unpausing, e.g. for an incident-response key
+    RateSetter, // Karma to XLM conversion rate
+    Moderator,  // Slashing stakes
+    Reporter,   // Recording activities
+}
 
 // User data structure
 #[contracttype]
@@ -81,17 +200,183 @@ const PAUSED: Symbol = symbol_short!("PAUSED"); // Contract pause status
 pub struct UserData {
     pub karma_points: i32,
     pub registered: bool,
+    pub last_update: u64, // Ledger timestamp karma_points was last decayed to
+}
+
+// Leaderboard place, karma is as of `updated_at`
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LeaderboardEntry {
+    pub user: Address,
+    pub karma: i32,
+    pub updated_at: u64,
 }
 
 // Activity record structure
 #[contracttype]
-#[derive(Clone)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct ActivityRecord {
     pub activity_type: ActivityType,
+    pub content_id: BytesN<32>,
+    pub author: Option<Address>, // Author of the content interacted with, on the author's own copy the user who interacted
     pub karma_change: i32,
     pub timestamp: u64,
 }
 
+// Settings of a token accepted for staking
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct StakeAsset {
+    pub weight: u32,   // Value of one unit relative to XLM, in basis points
+    pub decimals: u32, // Decimals of the token, used to normalize amounts
+}
+
+// A user's stake in one token
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AssetStake {
+    pub token: Address,
+    pub amount: i128, // Raw token amount
+    pub value: i128,  // Weighted value counted towards the multiplier
+}
+
+// Multiplier (1 decimal place) that applies from a stake value threshold upwards
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MultiplierTier {
+    pub threshold: i128,
+    pub multiplier: u32,
+}
+
+// How the multiplier grows above the top tier
+#[contracttype]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ScalingMode {
+    Flat, // Top tier multiplier applies as is
+    Log2, // +step per doubling of the stake over the top threshold
+    Sqrt, // +step per whole square root of the stake over the top threshold, minus one
+}
+
+// Staking multiplier curve
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MultiplierSchedule {
+    pub tiers: Vec<MultiplierTier>, // Sorted by threshold, the first one at 0
+    pub scaling: ScalingMode,
+    pub step: u32,
+    pub max_multiplier: u32,
+}
+
+// Staking reward settings
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RewardConfig {
+    pub rate: i128,           // Reward tokens streamed to all stakers per second
+    pub max_karma_boost: u32, // Extra reward shares for high-karma stakers, in basis points
+    pub boost_karma: i32,     // Karma at which the full boost applies
+}
+
+// Karma to XLM conversion rate: every `karma` points redeem for `stroops`
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct KarmaRate {
+    pub karma: u32,
+    pub stroops: i128,
+}
+
+// Caps on XLM paid out by `redeem_karma` per epoch, 0 disables a cap
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RedemptionLimits {
+    pub epoch: u64,       // Epoch length in seconds, epochs start at multiples of it
+    pub user_cap: i128,   // Stroops a single user can redeem per epoch
+    pub global_cap: i128, // Stroops all users together can redeem per epoch
+}
+
+// Redemptions of at least `threshold` stroops vest over `duration` seconds, 0 disables vesting
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct VestingConfig {
+    pub threshold: i128,
+    pub duration: u64,
+}
+
+// Redemption payout released linearly between `start` and `end`
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct VestingEntry {
+    pub amount: i128,
+    pub claimed: i128,
+    pub start: u64,
+    pub end: u64,
+}
+
+// Setting change that has to wait out the timelock delay
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ParamChange {
+    KarmaRate(KarmaRate),
+    MultiplierSchedule(MultiplierSchedule),
+    ActivityKind(Symbol, i32, bool), // Kind, base karma, enabled
+    TimelockDelay(u64),
+}
+
+// Scheduled change and the ledger timestamp it can be executed from
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PendingChange {
+    pub id: u32,
+    pub change: ParamChange,
+    pub eta: u64,
+}
+
+// Stake waiting out the unbonding period before it can be claimed
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct UnbondingEntry {
+    pub token: Address,
+    pub amount: i128,
+    pub release_at: u64, // Ledger timestamp the amount can be claimed from
+}
+
+// How `record_batch` handles entries that fail
+#[contracttype]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum BatchMode {
+    Atomic,     // Any failure reverts the whole batch
+    BestEffort, // Failed entries are reported and skipped
+}
+
+// One activity in a `record_batch` call
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BatchEntry {
+    pub user: Address,
+    pub activity_type: ActivityType,
+    pub content_id: BytesN<32>,
+    pub author: Option<Address>,
+}
+
+// Per-entry outcome of `record_batch`
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum BatchResult {
+    Applied(i32), // Karma change applied to the user
+    Failed(u32),  // KarmaError code
+}
+
+// Activity attestation signed off-chain by the attestor key
+#[contracttype]
+#[derive(Clone)]
+pub struct Attestation {
+    pub user: Address,
+    pub activity_type: ActivityType,
+    pub content_id: BytesN<32>,
+    pub author: Option<Address>,
+    pub nonce: u64,
+    pub expiry: u64,
+}
+
 #[contract]
 pub struct KarmaEngineContract;
 
@@ -99,14 +384,22 @@ pub struct KarmaEngineContract;
 impl KarmaEngineContract {
     /// Initialize the contract with an owner and XLM token contract
     pub fn initialize(e: Env, owner: Address, xlm_token: Address) -> Result<(), KarmaError> {
-        if e.storage().instance().has(&OWNER) {
+        // The owner can be renounced, the token is always there
+        if e.storage().instance().has(&KARMATOK) {
             return Err(KarmaError::AlreadyRegistered);
         }
         e.storage().instance().set(&OWNER, &owner);
         e.storage().instance().set(&KARMATOK, &xlm_token);
-        e.storage().instance().set(&KARMART, &DEFAULT_KARMA_TO_XLM_RATE);
+        e.storage().instance().set(&KARMARATE, &KarmaRate { karma: DEFAULT_RATE_KARMA, stroops: DEFAULT_RATE_STROOPS });
         e.storage().instance().set(&PAUSED, &false);
         
+        // XLM is always accepted as a stake token, at full weight
+        let xlm_asset = StakeAsset { weight: FULL_WEIGHT, decimals: STAKE_VALUE_DECIMALS };
+        storage::write_stake_asset(&e, &xlm_token, Some(&xlm_asset));
+        
+        // Keep contract settings alive
+        storage::extend_instance(&e);
+        
         // Emit event
         e.events().publish((symbol_short!("init"),), owner);
         
@@ -121,24 +414,24 @@ impl KarmaEngineContract {
             return Err(KarmaError::ContractPaused);
         }
         
-        let mut users: Map<Address, UserData> = e.storage().instance().get(&USERS).unwrap_or_else(|| Map::new(&e));
-        
-        if users.contains_key(user.clone()) {
+        if storage::has_user(&e, &user) {
             return Err(KarmaError::AlreadyRegistered);
         }
         
         let user_data = UserData {
             karma_points: 0,
             registered: true,
+            last_update: e.ledger().timestamp(),
         };
         
-        users.set(user.clone(), user_data);
-        e.storage().instance().set(&USERS, &users);
+        storage::write_user(&e, &user, &user_data);
         
         // Also initialize their stake
-        let mut stakes: Map<Address, i128> = e.storage().instance().get(&STAKES).unwrap_or_else(|| Map::new(&e));
-        stakes.set(user.clone(), 0);
-        e.storage().instance().set(&STAKES, &stakes);
+        storage::write_stakes(&e, &user, &Map::new(&e));
+        
+        // Keep contract settings and the user's entries alive
+        storage::extend_instance(&e);
+        storage::extend_user(&e, &user);
         
         // Emit event
         e.events().publish((symbol_short!("reg"),), user.clone());
@@ -151,20 +444,26 @@ impl KarmaEngineContract {
 
     /// Get user karma points
     pub fn get_karma(e: Env, user: Address) -> Result<i32, KarmaError> {
-        let users: Map<Address, UserData> = e.storage().instance().get(&USERS).unwrap_or_else(|| Map::new(&e));
-        
-        if !users.contains_key(user.clone()) {
-            return Err(KarmaError::NotRegistered);
-        }
-        
-        let user_data = users.get(user).unwrap();
+        let user_data = storage::read_decayed_user(&e, &user).ok_or(KarmaError::NotRegistered)?;
         Ok(user_data.karma_points)
     }
 
-    /// Get user staking amount
+    /// Get the weighted value of a user's stake over every stake token
     pub fn get_stake(e: Env, user: Address) -> Result<i128, KarmaError> {
-        let stakes: Map<Address, i128> = e.storage().instance().get(&STAKES).unwrap_or_else(|| Map::new(&e));
-        Ok(stakes.get(user).unwrap_or(0))
+        Ok(Self::stake_total(&e, &user))
+    }
+
+    /// Get a user's stake per token with the value each counts for
+    pub fn get_stakes(e: Env, user: Address) -> Result<Vec<AssetStake>, KarmaError> {
+        let mut stakes = Vec::new(&e);
+        for (token, amount) in storage::read_stakes(&e, &user).iter() {
+            // Tokens no longer accepted do not count towards the multiplier
+            let value = storage::read_stake_asset(&e, &token)
+                .map(|asset| Self::stake_value(amount, &asset))
+                .unwrap_or(0);
+            stakes.push_back(AssetStake { token, amount, value });
+        }
+        Ok(stakes)
     }
 
     /// Stake tokens to increase karma multiplier
@@ -177,24 +476,38 @@ impl KarmaEngineContract {
         
         user.require_auth();
         
+        if amount <= 0 {
+            return Err(KarmaError::InvalidAmount);
+        }
+        
+        // Only tokens accepted by the owner can be staked
+        if storage::read_stake_asset(&e, &token).is_none() {
+            return Err(KarmaError::UnsupportedToken);
+        }
+        
         // Transfer tokens from user to contract
         let token_client = TokenClient::new(&e, &token);
-        token_client.transfer(&user, &e.current_contract_address(), &amount);
+        token_client.transfer(&user, e.current_contract_address(), &amount);
         
-        // Update user's stake
-        let mut stakes: Map<Address, i128> = e.storage().instance().get(&STAKES).unwrap_or_else(|| Map::new(&e));
-        let current_stake = stakes.get(user.clone()).unwrap_or(0);
-        stakes.set(user.clone(), current_stake + amount);
-        e.storage().instance().set(&STAKES, &stakes);
+        // Update user's stake in this token
+        Self::sync_reward_shares(&e, &user); // Settle rewards on the shares held until now
+        let current_stake = storage::read_stake(&e, &user, &token);
+        storage::write_stake(&e, &user, &token, current_stake + amount);
+        Self::sync_reward_shares(&e, &user);
+        
+        // Keep contract settings and the user's entries alive
+        storage::extend_instance(&e);
+        storage::extend_user(&e, &user);
         
         // Emit event
-        e.events().publish((symbol_short!("stake"),), (user.clone(), amount));
+        e.events().publish((symbol_short!("stake"),), (user.clone(), token, amount));
         
         Ok(())
     }
 
-    /// Withdraw staked tokens
-    pub fn withdraw_stake(e: Env, user: Address, token: Address, amount: i128) -> Result<(), KarmaError> {
+    /// Start unbonding part of a stake. The multiplier drops right away and
+    /// the tokens can be claimed with `claim_unstaked` once the unbonding period has passed.
+    pub fn request_unstake(e: Env, user: Address, token: Address, amount: i128) -> Result<u64, KarmaError> {
         // Check if contract is paused
         let paused = Self::is_paused(e.clone())?;
         if paused {
@@ -203,251 +516,1275 @@ impl KarmaEngineContract {
         
         user.require_auth();
         
-        let mut stakes: Map<Address, i128> = e.storage().instance().get(&STAKES).unwrap_or_else(|| Map::new(&e));
-        let current_stake = stakes.get(user.clone()).unwrap_or(0);
+        if amount <= 0 {
+            return Err(KarmaError::InvalidAmount);
+        }
+        
+        // Tokens can only be unstaked from the user's own stake in that token
+        let current_stake = storage::read_stake(&e, &user, &token);
         
         if amount > current_stake {
             return Err(KarmaError::InsufficientBalance);
         }
         
         // Update user's stake
-        stakes.set(user.clone(), current_stake - amount);
-        e.storage().instance().set(&STAKES, &stakes);
+        Self::sync_reward_shares(&e, &user); // Settle rewards on the shares held until now
+        storage::write_stake(&e, &user, &token, current_stake - amount);
+        Self::sync_reward_shares(&e, &user);
         
-        // Transfer tokens back to user
-        let token_client = TokenClient::new(&e, &token);
-        token_client.transfer(&e.current_contract_address(), &user, &amount);
+        // Queue the amount until the unbonding period has passed
+        let release_at = e.ledger().timestamp() + storage::read_unbonding_period(&e);
+        let mut pending = storage::read_unbonding(&e, &user);
+        pending.push_back(UnbondingEntry { token, amount, release_at });
+        storage::write_unbonding(&e, &user, &pending);
+        
+        // Keep contract settings and the user's entries alive
+        storage::extend_instance(&e);
+        storage::extend_user(&e, &user);
         
         // Emit event
-        e.events().publish((symbol_short!("unstake"),), (user.clone(), amount));
+        e.events().publish((symbol_short!("unbond"),), (user.clone(), amount, release_at));
         
-        Ok(())
+        Ok(release_at)
     }
 
-    /// Record a post activity and update karma
-    pub fn record_post(e: Env, user: Address) -> Result<i32, KarmaError> {
+    /// Pay out every unbonding entry that has matured. Returns the amount claimed.
+    pub fn claim_unstaked(e: Env, user: Address) -> Result<i128, KarmaError> {
         // Check if contract is paused
         let paused = Self::is_paused(e.clone())?;
         if paused {
             return Err(KarmaError::ContractPaused);
         }
         
-        Self::record_activity(e.clone(), user, ActivityType::Post, POST_KARMA)
-    }
-
-    /// Record a comment activity and update karma
-    pub fn record_comment(e: Env, user: Address) -> Result<i32, KarmaError> {
-        // Check if contract is paused
-        let paused = Self::is_paused(e.clone())?;
-        if paused {
-            return Err(KarmaError::ContractPaused);
+        user.require_auth();
+        
+        let now = e.ledger().timestamp();
+        let mut pending = Vec::new(&e);
+        let mut matured = Vec::new(&e);
+        for entry in storage::read_unbonding(&e, &user).iter() {
+            if entry.release_at <= now {
+                matured.push_back(entry);
+            } else {
+                pending.push_back(entry);
+            }
         }
         
-        Self::record_activity(e.clone(), user, ActivityType::Comment, COMMENT_KARMA)
-    }
-
-    /// Record a like activity and update karma
-    pub fn record_like(e: Env, user: Address) -> Result<i32, KarmaError> {
-        // Check if contract is paused
-        let paused = Self::is_paused(e.clone())?;
-        if paused {
-            return Err(KarmaError::ContractPaused);
+        if matured.is_empty() {
+            return Err(KarmaError::StillUnbonding);
+        }
+        
+        storage::write_unbonding(&e, &user, &pending);
+        
+        // Transfer tokens back to user
+        let mut claimed = 0;
+        for entry in matured.iter() {
+            let token_client = TokenClient::new(&e, &entry.token);
+            token_client.transfer(&e.current_contract_address(), &user, &entry.amount);
+            claimed += entry.amount;
         }
         
-        Self::record_activity(e.clone(), user, ActivityType::Like, LIKE_KARMA)
+        // Keep contract settings and the user's entries alive
+        storage::extend_instance(&e);
+        storage::extend_user(&e, &user);
+        
+        // Emit event
+        e.events().publish((symbol_short!("unstake"),), (user.clone(), claimed));
+        
+        Ok(claimed)
     }
 
-    /// Record a repost activity and update karma
-    pub fn record_repost(e: Env, user: Address) -> Result<i32, KarmaError> {
-        // Check if contract is paused
-        let paused = Self::is_paused(e.clone())?;
-        if paused {
-            return Err(KarmaError::ContractPaused);
+    /// Add XLM to the staking reward pool (anyone can fund it)
+    pub fn fund_stake_rewards(e: Env, funder: Address, amount: i128) -> Result<(), KarmaError> {
+        funder.require_auth();
+        
+        if amount <= 0 {
+            return Err(KarmaError::InvalidAmount);
         }
         
-        Self::record_activity(e.clone(), user, ActivityType::Repost, REPOST_KARMA)
+        // Transfer tokens from funder to contract
+        let xlm_token: Address = e.storage().instance().get(&KARMATOK).unwrap();
+        let token_client = TokenClient::new(&e, &xlm_token);
+        token_client.transfer(&funder, e.current_contract_address(), &amount);
+        
+        rewards::fund(&e, amount);
+        
+        // Keep contract settings alive
+        storage::extend_instance(&e);
+        
+        // Emit event
+        e.events().publish((symbol_short!("rwd_fund"),), (funder, amount));
+        
+        Ok(())
     }
 
-    /// Record a report activity and update karma (negative)
-    pub fn record_report(e: Env, user: Address) -> Result<i32, KarmaError> {
+    /// Pay out a staker's accrued rewards in XLM. Returns the amount claimed.
+    pub fn claim_stake_rewards(e: Env, user: Address) -> Result<i128, KarmaError> {
         // Check if contract is paused
         let paused = Self::is_paused(e.clone())?;
         if paused {
             return Err(KarmaError::ContractPaused);
         }
         
-        Self::record_activity(e.clone(), user, ActivityType::Report, REPORT_PENALTY)
+        user.require_auth();
+        
+        // Settle at the user's current shares, picking up decay and delisted tokens
+        let amount = rewards::take(&e, &user, &Self::reward_shares(&e, &user));
+        
+        if amount > 0 {
+            let xlm_token: Address = e.storage().instance().get(&KARMATOK).unwrap();
+            let token_client = TokenClient::new(&e, &xlm_token);
+            token_client.transfer(&e.current_contract_address(), &user, &amount);
+        }
+        
+        // Keep contract settings and the user's entries alive
+        storage::extend_instance(&e);
+        storage::extend_user(&e, &user);
+        
+        // Emit event
+        e.events().publish((symbol_short!("rwd_claim"),), (user, amount));
+        
+        Ok(amount)
     }
 
-    /// Get user's activity history (newest first)
-    pub fn get_activities(e: Env, user: Address) -> Result<Vec<ActivityRecord>, KarmaError> {
-        let activities_store: Map<Address, Vec<ActivityRecord>> = e.storage().instance().get(&ACTIVITY).unwrap_or_else(|| Map::new(&e));
-        Ok(activities_store.get(user).unwrap_or_else(|| Vec::new(&e)))
+    /// Get the staking rewards a user could claim right now
+    pub fn pending_stake_rewards(e: Env, user: Address) -> Result<i128, KarmaError> {
+        Ok(rewards::pending(&e, &user, &Self::reward_shares(&e, &user)))
     }
 
-    /// Get user's karma multiplier based on their stake with normalized tiers
-    pub fn get_multiplier(e: Env, user: Address) -> Result<u32, KarmaError> {
-        let stake = Self::get_stake(e, user)?;
-        
-        // Normalize staking via simplified scaling to prevent whale dominance
-        if stake >= INFLUENCER_TIER_THRESHOLD {
-            // For large stakes, use simplified scaling
-            // Instead of logarithmic, we'll use a simple formula that scales slower
-            let scaled_stake = stake / 100; // Reduce the impact of large stakes
-            let multiplier = INFLUENCER_MULTIPLIER + (scaled_stake as u32).min(30); // Cap additional multiplier at 30
-            // Cap the total multiplier to prevent excessive influence
-            Ok(multiplier.min(50)) // Maximum 5x multiplier
-        } else if stake >= TRUSTED_TIER_THRESHOLD {
-            Ok(TRUSTED_MULTIPLIER)
-        } else {
-            Ok(REGULAR_MULTIPLIER)
-        }
+    /// Get the staking reward settings
+    pub fn get_stake_rewards(e: Env) -> Result<RewardConfig, KarmaError> {
+        Ok(storage::read_reward_config(&e))
     }
 
-    /// Redeem karma points for XLM tokens
-    pub fn redeem_karma(e: Env, user: Address, karma_amount: i32) -> Result<(), KarmaError> {
-        // Check if contract is paused
-        let paused = Self::is_paused(e.clone())?;
-        if paused {
-            return Err(KarmaError::ContractPaused);
+    /// Confiscate a share of every stake and unbonding entry of a user into the
+    /// treasury for a confirmed violation on `content_id`, and take the same share
+    /// of their karma (owner or moderator only). The share is the slash rate for
+    /// users without karma and shrinks linearly to half of it at `SLASH_RELIEF_KARMA`.
+    pub fn slash(e: Env, moderator: Address, user: Address, content_id: BytesN<32>) -> Result<(), KarmaError> {
+        roles::require(&e, Role::Moderator, &moderator)?;
+        
+        // Weigh the slash rate by the user's karma
+        let user_data = storage::read_decayed_user(&e, &user);
+        let karma = user_data.as_ref().map(|data| data.karma_points).unwrap_or(0);
+        let relief = karma.clamp(0, SLASH_RELIEF_KARMA) as i128;
+        let full_rate = storage::read_slash_rate(&e).unwrap_or(DEFAULT_SLASH_RATE) as i128;
+        let rate = full_rate * (2 * SLASH_RELIEF_KARMA as i128 - relief) / (2 * SLASH_RELIEF_KARMA as i128);
+        let mut slashed = Map::new(&e);
+        
+        // Staked tokens
+        let mut stakes = storage::read_stakes(&e, &user);
+        for (token, amount) in stakes.clone().iter() {
+            let cut = amount * rate / FULL_WEIGHT as i128;
+            if cut > 0 {
+                slashed.set(token.clone(), slashed.get(token.clone()).unwrap_or(0) + cut);
+                if amount == cut {
+                    stakes.remove(token);
+                } else {
+                    stakes.set(token, amount - cut);
+                }
+            }
         }
         
-        user.require_auth();
+        // Tokens still unbonding can be slashed too
+        let mut pending = Vec::new(&e);
+        for mut entry in storage::read_unbonding(&e, &user).iter() {
+            let cut = entry.amount * rate / FULL_WEIGHT as i128;
+            if cut > 0 {
+                slashed.set(entry.token.clone(), slashed.get(entry.token.clone()).unwrap_or(0) + cut);
+                entry.amount -= cut;
+            }
+            if entry.amount > 0 {
+                pending.push_back(entry);
+            }
+        }
         
-        // Check if user is registered
-        let mut users: Map<Address, UserData> = e.storage().instance().get(&USERS).unwrap_or_else(|| Map::new(&e));
+        if slashed.is_empty() {
+            return Err(KarmaError::NothingToSlash);
+        }
         
-        if !users.contains_key(user.clone()) {
-            return Err(KarmaError::NotRegistered);
+        Self::sync_reward_shares(&e, &user); // Settle rewards on the shares held until now
+        storage::write_stakes(&e, &user, &stakes);
+        storage::write_unbonding(&e, &user, &pending);
+        Self::sync_reward_shares(&e, &user);
+        
+        for (token, amount) in slashed.iter() {
+            storage::write_treasury(&e, &token, storage::read_treasury(&e, &token) + amount);
+            
+            // Emit event
+            e.events().publish((symbol_short!("slashed"), user.clone()), (token, amount));
         }
         
-        // Check if user has enough karma
-        let mut user_data = users.get(user.clone()).unwrap();
-        if user_data.karma_points < karma_amount {
-            return Err(KarmaError::InsufficientKarma);
+        // Take the same share of the user's karma and record the slash in their history
+        let penalty = (karma.max(0) as i128 * rate / FULL_WEIGHT as i128) as i32;
+        let activity_record = ActivityRecord {
+            activity_type: ActivityType::Slash,
+            content_id,
+            author: None,
+            karma_change: -penalty,
+            timestamp: e.ledger().timestamp(),
+        };
+        match user_data {
+            Some(user_data) => Self::apply_karma(&e, &user, user_data, activity_record),
+            None => storage::push_activity(&e, &user, activity_record),
         }
         
-        // Calculate XLM amount using current conversion rate
-        let karma_rate: i128 = e.storage().instance().get(&KARMART).unwrap_or(DEFAULT_KARMA_TO_XLM_RATE);
-        let xlm_amount = (karma_amount as i128) / karma_rate;
-        let _leftover_karma = (karma_amount as i128) % karma_rate; // Track leftover karma for future use
+        // Keep contract settings and the user's entries alive
+        storage::extend_instance(&e);
+        storage::extend_user(&e, &user);
         
-        if xlm_amount == 0 {
+        Ok(())
+    }
+
+    /// Get the share of a stake slashed on a confirmed violation, in basis points
+    pub fn get_slash_rate(e: Env) -> Result<u32, KarmaError> {
+        Ok(storage::read_slash_rate(&e).unwrap_or(DEFAULT_SLASH_RATE))
+    }
+
+    /// Get the amount of a token held by the treasury
+    pub fn treasury_balance(e: Env, token: Address) -> Result<i128, KarmaError> {
+        Ok(storage::read_treasury(&e, &token))
+    }
+
+    /// Add XLM to the treasury that pays out karma redemptions
+    pub fn fund_treasury(e: Env, funder: Address, amount: i128) -> Result<(), KarmaError> {
+        funder.require_auth();
+        
+        if amount <= 0 {
             return Err(KarmaError::InvalidAmount);
         }
         
-        // Deduct karma from user (including leftover karma that can't be converted)
-        user_data.karma_points -= karma_amount;
-        users.set(user.clone(), user_data);
-        e.storage().instance().set(&USERS, &users);
-        
-        // Transfer XLM tokens to user
+        // Transfer tokens from funder to contract
         let xlm_token: Address = e.storage().instance().get(&KARMATOK).unwrap();
         let token_client = TokenClient::new(&e, &xlm_token);
-        token_client.transfer(&e.current_contract_address(), &user, &xlm_amount);
+        token_client.transfer(&funder, e.current_contract_address(), &amount);
         
-        // Emit events
-        e.events().publish((symbol_short!("redeem"),), (user.clone(), karma_amount, xlm_amount));
-        e.events().publish((symbol_short!("karma_red"),), KarmaEvent::KarmaRedeemed(user.clone(), karma_amount as i128, xlm_amount));
+        let balance = storage::read_treasury(&e, &xlm_token);
+        storage::write_treasury(&e, &xlm_token, balance + amount);
+        
+        // Keep contract settings alive
+        storage::extend_instance(&e);
+        
+        // Emit event
+        e.events().publish((symbol_short!("trs_fund"),), (funder, amount));
         
         Ok(())
     }
 
-    /// Get the XLM token contract address
-    pub fn get_xlm_token(e: Env) -> Result<Address, KarmaError> {
-        Ok(e.storage().instance().get(&KARMATOK).unwrap())
+    /// Get a user's stake still waiting out the unbonding period, oldest first
+    pub fn get_unbonding(e: Env, user: Address) -> Result<Vec<UnbondingEntry>, KarmaError> {
+        Ok(storage::read_unbonding(&e, &user))
     }
 
-    /// Get the current karma to XLM conversion rate
-    pub fn get_karma_rate(e: Env) -> Result<i128, KarmaError> {
-        Ok(e.storage().instance().get(&KARMART).unwrap_or(DEFAULT_KARMA_TO_XLM_RATE))
+    /// Record an activity of any kind defined in the activity catalogue (reporter only).
+    /// `content_id` identifies the content acted on; each user can only record a
+    /// kind once per content. Comments, likes and reposts also name its author.
+    pub fn record(e: Env, reporter: Address, user: Address, kind: Symbol, content_id: BytesN<32>, author: Option<Address>) -> Result<i32, KarmaError> {
+        // Check if contract is paused
+        let paused = Self::is_paused(e.clone())?;
+        if paused {
+            return Err(KarmaError::ContractPaused);
+        }
+        
+        roles::require(&e, Role::Reporter, &reporter)?;
+        
+        let activity_type = ActivityType::from_symbol(kind);
+        let base_karma = Self::base_karma(&e, &activity_type)?;
+        Self::record_activity(e.clone(), user, activity_type, base_karma, content_id, author)
     }
 
-    /// Check if contract is paused
-    pub fn is_paused(e: Env) -> Result<bool, KarmaError> {
-        Ok(e.storage().instance().get(&PAUSED).unwrap_or(false))
+    /// Record a post activity and update karma (reporter only)
+    pub fn record_post(e: Env, reporter: Address, user: Address, content_id: BytesN<32>) -> Result<i32, KarmaError> {
+        Self::record(e, reporter, user, symbol_short!("post"), content_id, None)
     }
 
-    /// Get the total karma of all users (for leaderboard)
-    pub fn total_karma(e: Env) -> Result<i32, KarmaError> {
-        let users: Map<Address, UserData> = e.storage().instance().get(&USERS).unwrap_or_else(|| Map::new(&e));
-        let mut total = 0;
-        for user_data in users.values() {
-            total += user_data.karma_points;
-        }
-        Ok(total)
+    /// Record a comment on `author`'s content and update karma (reporter only)
+    pub fn record_comment(e: Env, reporter: Address, user: Address, content_id: BytesN<32>, author: Address) -> Result<i32, KarmaError> {
+        Self::record(e, reporter, user, symbol_short!("comment"), content_id, Some(author))
     }
 
-    /// Get how much XLM a user can redeem based on their current karma and rate
-    pub fn redeemable_balance(e: Env, user: Address) -> Result<i128, KarmaError> {
-        let karma = Self::get_karma(e.clone(), user)? as i128;
-        let karma_rate: i128 = e.storage().instance().get(&KARMART).unwrap_or(DEFAULT_KARMA_TO_XLM_RATE);
-        Ok(karma / karma_rate)
+    /// Record a like of `author`'s content and update karma (reporter only)
+    pub fn record_like(e: Env, reporter: Address, user: Address, content_id: BytesN<32>, author: Address) -> Result<i32, KarmaError> {
+        Self::record(e, reporter, user, symbol_short!("like"), content_id, Some(author))
     }
 
-    /// ADMIN FUNCTIONS ///
+    /// Record a repost of `author`'s content and update karma (reporter only)
+    pub fn record_repost(e: Env, reporter: Address, user: Address, content_id: BytesN<32>, author: Address) -> Result<i32, KarmaError> {
+        Self::record(e, reporter, user, symbol_short!("repost"), content_id, Some(author))
+    }
 
-    /// Pause/unpause the contract (admin only)
-    pub fn set_paused(e: Env, admin: Address, paused: bool) -> Result<(), KarmaError> {
-        admin.require_auth();
-        
-        let owner: Address = e.storage().instance().get(&OWNER).unwrap();
-        if admin != owner {
-            return Err(KarmaError::Unauthorized);
-        }
+    /// Record a report activity and update karma (negative, reporter only)
+    pub fn record_report(e: Env, reporter: Address, user: Address, content_id: BytesN<32>) -> Result<i32, KarmaError> {
+        Self::record(e, reporter, user, symbol_short!("report"), content_id, None)
+    }
+
+    /// Record many activities in one invocation (reporter only).
+    /// Returns the karma change (or error code) of each entry, in order.
+    pub fn record_batch(e: Env, reporter: Address, entries: Vec<BatchEntry>, mode: BatchMode) -> Result<Vec<BatchResult>, KarmaError> {
+        roles::require(&e, Role::Reporter, &reporter)?;
         
-        e.storage().instance().set(&PAUSED, &paused);
+        let paused = Self::is_paused(e.clone())?;
         
-        // Emit events
-        e.events().publish((symbol_short!("pause"),), paused);
-        e.events().publish((symbol_short!("con_pause"),), KarmaEvent::ContractPaused(paused));
+        let mut results = Vec::new(&e);
+        for entry in entries.iter() {
+            let outcome = if paused {
+                Err(KarmaError::ContractPaused)
+            } else {
+                // Built-in kinds passed as `Custom` are handled like the built-in type
+                let activity_type = ActivityType::from_symbol(entry.activity_type.symbol());
+                Self::base_karma(&e, &activity_type).and_then(|base_karma| {
+                    Self::record_activity(e.clone(), entry.user, activity_type, base_karma, entry.content_id, entry.author)
+                })
+            };
+            
+            match outcome {
+                Ok(karma_change) => results.push_back(BatchResult::Applied(karma_change)),
+                Err(err) if mode == BatchMode::BestEffort => results.push_back(BatchResult::Failed(err as u32)),
+                Err(err) => return Err(err),
+            }
+        }
         
-        Ok(())
+        Ok(results)
     }
 
-    /// Adjust the karma to XLM conversion rate (admin only)
-    pub fn set_karma_rate(e: Env, admin: Address, rate: i128) -> Result<(), KarmaError> {
-        admin.require_auth();
+    /// Record an activity from an attestation signed by the attestor key.
+    /// Anyone can relay it; the signature covers `(contract address, attestation)`
+    /// and each nonce can be used once per user, in any order. An invalid
+    /// signature traps in the host instead of returning a `KarmaError`.
+    pub fn submit_attestation(e: Env, attestation: Attestation, signature: BytesN<64>) -> Result<i32, KarmaError> {
+        // Check if contract is paused
+        let paused = Self::is_paused(e.clone())?;
+        if paused {
+            return Err(KarmaError::ContractPaused);
+        }
         
-        let owner: Address = e.storage().instance().get(&OWNER).unwrap();
-        if admin != owner {
-            return Err(KarmaError::Unauthorized);
+        let attestor: BytesN<32> = e.storage().instance().get(&ATTESTOR).ok_or(KarmaError::Unauthorized)?;
+        
+        if e.ledger().timestamp() > attestation.expiry {
+            return Err(KarmaError::AttestationExpired);
         }
         
-        if rate <= 0 {
-            return Err(KarmaError::InvalidAmount);
+        let user = attestation.user.clone();
+        if storage::is_nonce_used(&e, &user, attestation.nonce) {
+            return Err(KarmaError::InvalidNonce);
         }
         
-        e.storage().instance().set(&KARMART, &rate);
+        // Verify the signature over the attestation bound to this contract
+        let payload = (e.current_contract_address(), attestation.clone()).to_xdr(&e);
+        e.crypto().ed25519_verify(&attestor, &payload, &signature);
         
-        // Emit event
-        e.events().publish((symbol_short!("rate"),), rate);
+        storage::mark_nonce_used(&e, &user, attestation.nonce);
         
-        Ok(())
+        // Built-in kinds passed as `Custom` are handled like the built-in type
+        let activity_type = ActivityType::from_symbol(attestation.activity_type.symbol());
+        let base_karma = Self::base_karma(&e, &activity_type)?;
+        Self::record_activity(e.clone(), user, activity_type, base_karma, attestation.content_id, attestation.author)
     }
 
-    /// Reset a user's karma and stake (admin only, for testing)
-    pub fn reset_user(e: Env, admin: Address, user: Address) -> Result<(), KarmaError> {
-        admin.require_auth();
+    /// Get the catalogue entry of an activity kind (built-in kinds fall back to their defaults)
+    pub fn get_activity_kind(e: Env, kind: Symbol) -> Result<ActivityKind, KarmaError> {
+        if let Some(activity_kind) = storage::read_activity_kind(&e, &kind) {
+            return Ok(activity_kind);
+        }
+        
+        let base_karma = match ActivityType::from_symbol(kind) {
+            ActivityType::Post => POST_KARMA,
+            ActivityType::Comment => COMMENT_KARMA,
+            ActivityType::Like => LIKE_KARMA,
+            ActivityType::Repost => REPOST_KARMA,
+            ActivityType::Report => REPORT_PENALTY,
+            ActivityType::Custom(_) | ActivityType::Slash => return Err(KarmaError::UnknownActivity),
+        };
+        Ok(ActivityKind { base_karma, enabled: true })
+    }
+
+    /// Check whether an attestation nonce was already used for a user
+    pub fn is_nonce_used(e: Env, user: Address, nonce: u64) -> Result<bool, KarmaError> {
+        Ok(storage::is_nonce_used(&e, &user, nonce))
+    }
+
+    /// Get user's activity history (newest first)
+    pub fn get_activities(e: Env, user: Address) -> Result<Vec<ActivityRecord>, KarmaError> {
+        Ok(storage::read_activities(&e, &user))
+    }
+
+    /// Get user's karma multiplier based on the value of their stake and the multiplier schedule
+    pub fn get_multiplier(e: Env, user: Address) -> Result<u32, KarmaError> {
+        let stake = Self::get_stake(e.clone(), user)?;
+        let schedule = Self::get_multiplier_schedule(e)?;
+        Ok(multiplier::multiplier(&schedule, stake))
+    }
+
+    /// Get the staking multiplier schedule
+    pub fn get_multiplier_schedule(e: Env) -> Result<MultiplierSchedule, KarmaError> {
+        Ok(storage::read_multiplier_schedule(&e).unwrap_or_else(|| MultiplierSchedule {
+            tiers: vec![
+                &e,
+                MultiplierTier { threshold: 0, multiplier: REGULAR_MULTIPLIER },
+                MultiplierTier { threshold: TRUSTED_TIER_THRESHOLD, multiplier: TRUSTED_MULTIPLIER },
+                MultiplierTier { threshold: INFLUENCER_TIER_THRESHOLD, multiplier: INFLUENCER_MULTIPLIER },
+            ],
+            scaling: ScalingMode::Log2,
+            step: DEFAULT_SCALING_STEP,
+            max_multiplier: MAX_MULTIPLIER,
+        }))
+    }
+
+    /// Redeem karma points for XLM tokens and return the amount paid out.
+    /// Only whole multiples of the rate's karma are converted; the rest stays
+    /// on the user's balance. Payouts above the vesting threshold are not
+    /// transferred but vest, see `claim_vested`.
+    pub fn redeem_karma(e: Env, user: Address, karma_amount: i32) -> Result<i128, KarmaError> {
+        // Check if contract is paused
+        let paused = Self::is_paused(e.clone())?;
+        if paused {
+            return Err(KarmaError::ContractPaused);
+        }
+        
+        user.require_auth();
+        
+        if karma_amount <= 0 {
+            return Err(KarmaError::InvalidAmount);
+        }
+        
+        // Check if user is registered
+        let mut user_data = storage::read_decayed_user(&e, &user).ok_or(KarmaError::NotRegistered)?;
+        
+        // Check if user has enough karma
+        if user_data.karma_points < karma_amount {
+            return Err(KarmaError::InsufficientKarma);
+        }
+        
+        // Calculate XLM amount using current conversion rate
+        let rate = Self::karma_rate(&e);
+        let (karma_spent, xlm_amount) = Self::convert_karma(&rate, karma_amount)?;
+        if xlm_amount == 0 {
+            return Err(KarmaError::InvalidAmount);
+        }
+        
+        // Pay out of the treasury only, never out of staked deposits or staking rewards
+        let xlm_token: Address = e.storage().instance().get(&KARMATOK).unwrap();
+        let treasury = storage::read_treasury(&e, &xlm_token);
+        if treasury < xlm_amount {
+            return Err(KarmaError::TreasuryInsufficient);
+        }
+        storage::write_treasury(&e, &xlm_token, treasury - xlm_amount);
+        
+        // Enforce the per-epoch redemption caps
+        redemption::check(&e, &user, xlm_amount, rate.stroops)?;
+        
+        // Deduct only the converted karma, the leftover stays with the user
+        Self::sync_reward_shares(&e, &user); // Settle rewards on the shares held until now
+        user_data.karma_points -= karma_spent;
+        storage::write_user(&e, &user, &user_data);
+        storage::add_total_karma(&e, -karma_spent);
+        leaderboard::update(&e, &user, &user_data);
+        Self::sync_reward_shares(&e, &user);
+        
+        let vesting = storage::read_vesting_config(&e);
+        if vesting.threshold > 0 && xlm_amount >= vesting.threshold {
+            // Stream large payouts over the vesting period
+            let start = e.ledger().timestamp();
+            let end = start + vesting.duration;
+            let mut entries = storage::read_vesting(&e, &user);
+            entries.push_back(VestingEntry { amount: xlm_amount, claimed: 0, start, end });
+            storage::write_vesting(&e, &user, &entries);
+            e.events().publish((symbol_short!("vest"),), (user.clone(), xlm_amount, end));
+        } else {
+            // Transfer XLM tokens to user
+            let token_client = TokenClient::new(&e, &xlm_token);
+            token_client.transfer(&e.current_contract_address(), &user, &xlm_amount);
+        }
+        
+        // Keep contract settings and the user's entries alive
+        storage::extend_instance(&e);
+        storage::extend_user(&e, &user);
+        
+        // Emit events
+        e.events().publish((symbol_short!("redeem"),), (user.clone(), karma_spent, xlm_amount));
+        e.events().publish((symbol_short!("karma_red"),), KarmaEvent::KarmaRedeemed(user.clone(), karma_spent as i128, xlm_amount));
+        
+        Ok(xlm_amount)
+    }
+
+    /// Redeem karma points for KARMA tokens, one whole token per karma point.
+    /// Returns the amount minted in the token's smallest unit.
+    pub fn redeem_to_token(e: Env, user: Address, karma_amount: i32) -> Result<i128, KarmaError> {
+        // Check if contract is paused
+        let paused = Self::is_paused(e.clone())?;
+        if paused {
+            return Err(KarmaError::ContractPaused);
+        }
+        
+        user.require_auth();
+        
+        if karma_amount <= 0 {
+            return Err(KarmaError::InvalidAmount);
+        }
+        
+        let karma_token: Address = e.storage().instance().get(&REPTOKEN).ok_or(KarmaError::KarmaTokenNotSet)?;
+        
+        // Check if user is registered
+        let mut user_data = storage::read_decayed_user(&e, &user).ok_or(KarmaError::NotRegistered)?;
+        
+        // Check if user has enough karma
+        if user_data.karma_points < karma_amount {
+            return Err(KarmaError::InsufficientKarma);
+        }
+        
+        // Scale whole karma points to the token's decimals
+        let decimals = TokenClient::new(&e, &karma_token).decimals();
+        let token_amount = 10i128
+            .checked_pow(decimals)
+            .and_then(|unit| unit.checked_mul(karma_amount as i128))
+            .ok_or(KarmaError::InvalidAmount)?;
+        
+        // Deduct karma from user
+        Self::sync_reward_shares(&e, &user); // Settle rewards on the shares held until now
+        user_data.karma_points -= karma_amount;
+        storage::write_user(&e, &user, &user_data);
+        storage::add_total_karma(&e, -karma_amount);
+        leaderboard::update(&e, &user, &user_data);
+        Self::sync_reward_shares(&e, &user);
+        
+        // Mint KARMA tokens to user
+        KarmaTokenClient::new(&e, &karma_token).mint(&user, &token_amount);
+        
+        // Keep contract settings and the user's entries alive
+        storage::extend_instance(&e);
+        storage::extend_user(&e, &user);
+        
+        // Emit event
+        e.events().publish((symbol_short!("redeem_tk"),), (user, karma_amount, token_amount));
+        
+        Ok(token_amount)
+    }
+
+    /// Get the KARMA token contract address, if one is set
+    pub fn get_karma_token(e: Env) -> Result<Option<Address>, KarmaError> {
+        Ok(e.storage().instance().get(&REPTOKEN))
+    }
+
+    /// Pay out the vested part of a user's redemptions. Returns the amount claimed.
+    pub fn claim_vested(e: Env, user: Address) -> Result<i128, KarmaError> {
+        // Check if contract is paused
+        let paused = Self::is_paused(e.clone())?;
+        if paused {
+            return Err(KarmaError::ContractPaused);
+        }
+        
+        user.require_auth();
+        
+        let now = e.ledger().timestamp();
+        let mut remaining = Vec::new(&e);
+        let mut claimed = 0;
+        for mut entry in storage::read_vesting(&e, &user).iter() {
+            let vested = Self::vested(&entry, now);
+            claimed += vested - entry.claimed;
+            entry.claimed = vested;
+            if entry.claimed < entry.amount {
+                remaining.push_back(entry);
+            }
+        }
+        storage::write_vesting(&e, &user, &remaining);
+        
+        // Transfer XLM tokens to user
+        if claimed > 0 {
+            let xlm_token: Address = e.storage().instance().get(&KARMATOK).unwrap();
+            let token_client = TokenClient::new(&e, &xlm_token);
+            token_client.transfer(&e.current_contract_address(), &user, &claimed);
+        }
+        
+        // Keep contract settings and the user's entries alive
+        storage::extend_instance(&e);
+        storage::extend_user(&e, &user);
+        
+        // Emit event
+        e.events().publish((symbol_short!("vst_claim"),), (user, claimed));
+        
+        Ok(claimed)
+    }
+
+    /// Get a user's vesting redemptions
+    pub fn get_vesting(e: Env, user: Address) -> Result<Vec<VestingEntry>, KarmaError> {
+        Ok(storage::read_vesting(&e, &user))
+    }
+
+    /// Get how much of a user's vesting redemptions could be claimed right now
+    pub fn claimable_vested(e: Env, user: Address) -> Result<i128, KarmaError> {
+        let now = e.ledger().timestamp();
+        let mut claimable = 0;
+        for entry in storage::read_vesting(&e, &user).iter() {
+            claimable += Self::vested(&entry, now) - entry.claimed;
+        }
+        Ok(claimable)
+    }
+
+    /// Get the vesting threshold and duration
+    pub fn get_vesting_config(e: Env) -> Result<VestingConfig, KarmaError> {
+        Ok(storage::read_vesting_config(&e))
+    }
+
+    /// Get the XLM token contract address
+    pub fn get_xlm_token(e: Env) -> Result<Address, KarmaError> {
+        Ok(e.storage().instance().get(&KARMATOK).unwrap())
+    }
+
+    /// Get the current karma to XLM conversion rate
+    pub fn get_karma_rate(e: Env) -> Result<KarmaRate, KarmaError> {
+        Ok(Self::karma_rate(&e))
+    }
+
+    /// Check if a token can be staked
+    pub fn is_stake_token(e: Env, token: Address) -> Result<bool, KarmaError> {
+        Ok(storage::read_stake_asset(&e, &token).is_some())
+    }
+
+    /// Get the weight and decimals of an accepted stake token
+    pub fn get_stake_asset(e: Env, token: Address) -> Result<StakeAsset, KarmaError> {
+        storage::read_stake_asset(&e, &token).ok_or(KarmaError::UnsupportedToken)
+    }
+
+    /// Check if contract is paused
+    pub fn is_paused(e: Env) -> Result<bool, KarmaError> {
+        Ok(e.storage().instance().get(&PAUSED).unwrap_or(false))
+    }
+
+    /// Get the total karma of all users (for leaderboard)
+    pub fn total_karma(e: Env) -> Result<i32, KarmaError> {
+        Ok(storage::read_total_karma(&e))
+    }
+
+    /// Get a page of the leaderboard, highest karma first
+    pub fn get_leaderboard(e: Env, offset: u32, limit: u32) -> Result<Vec<LeaderboardEntry>, KarmaError> {
+        Ok(leaderboard::page(&e, offset, limit))
+    }
+
+    /// Get a user's 1-based leaderboard rank, or None if they are not on it
+    pub fn get_rank(e: Env, user: Address) -> Result<Option<u32>, KarmaError> {
+        Ok(leaderboard::rank(&e, &user))
+    }
+
+    /// Get the number of places kept on the leaderboard
+    pub fn get_leaderboard_size(e: Env) -> Result<u32, KarmaError> {
+        Ok(storage::read_leaderboard_size(&e))
+    }
+
+    /// Get the cooldown in seconds between two activities of a kind by the same user
+    pub fn get_cooldown(e: Env, kind: Symbol) -> Result<u64, KarmaError> {
+        Ok(storage::read_cooldown(&e, &kind))
+    }
+
+    /// Get the cap on karma a user can gain in a rolling 24h window (0 means no cap)
+    pub fn get_daily_cap(e: Env) -> Result<i32, KarmaError> {
+        Ok(storage::read_daily_cap(&e))
+    }
+
+    /// Check if a user is exempt from rate limits
+    pub fn is_rate_exempt(e: Env, user: Address) -> Result<bool, KarmaError> {
+        Ok(storage::is_rate_exempt(&e, &user))
+    }
+
+    /// Get how long unstaked tokens stay locked, in seconds
+    pub fn get_unbonding_period(e: Env) -> Result<u64, KarmaError> {
+        Ok(storage::read_unbonding_period(&e))
+    }
+
+    /// Get the share of interaction karma credited to the content's author, in basis points
+    pub fn get_author_share(e: Env) -> Result<u32, KarmaError> {
+        Ok(storage::read_author_share(&e))
+    }
+
+    /// Get the karma half-life in seconds (0 means karma never decays)
+    pub fn get_decay_half_life(e: Env) -> Result<u64, KarmaError> {
+        Ok(storage::read_half_life(&e))
+    }
+
+    /// Get how much XLM a user can redeem based on their current karma and rate
+    pub fn redeemable_balance(e: Env, user: Address) -> Result<i128, KarmaError> {
+        let karma = Self::get_karma(e.clone(), user)?;
+        if karma <= 0 {
+            return Ok(0);
+        }
+        let (_, xlm_amount) = Self::convert_karma(&Self::karma_rate(&e), karma)?;
+        Ok(xlm_amount)
+    }
+
+    /// Get the redemption caps and epoch length
+    pub fn get_redemption_limits(e: Env) -> Result<RedemptionLimits, KarmaError> {
+        Ok(storage::read_redemption_limits(&e))
+    }
+
+    /// Get how much XLM a user can still redeem in the current epoch under the
+    /// per-user and global caps, or nothing when no cap is set
+    pub fn redemption_allowance(e: Env, user: Address) -> Result<Option<i128>, KarmaError> {
+        Ok(redemption::allowance(&e, &user))
+    }
+
+    /// Extend the TTL of a user's entries so they are not archived (anyone can pay the rent)
+    pub fn bump_user(e: Env, user: Address) -> Result<(), KarmaError> {
+        if !storage::has_user(&e, &user) {
+            return Err(KarmaError::NotRegistered);
+        }
+        
+        storage::extend_instance(&e);
+        storage::extend_user(&e, &user);
+        
+        Ok(())
+    }
+
+    /// Apply a scheduled change once its delay has passed (anyone can execute it)
+    pub fn execute_change(e: Env, id: u32) -> Result<(), KarmaError> {
+        let pending = timelock::get(&e, id)?;
+        if e.ledger().timestamp() < pending.eta {
+            return Err(KarmaError::ChangeNotReady);
+        }
+        
+        timelock::remove(&e, id);
+        Self::apply_change(&e, pending.change)?;
+        
+        // Emit event
+        e.events().publish((symbol_short!("tl_exec"), id), ());
+        
+        Ok(())
+    }
+
+    /// Get the scheduled changes with the timestamp each can be executed from, oldest first
+    pub fn get_pending_changes(e: Env) -> Result<Vec<PendingChange>, KarmaError> {
+        Ok(storage::read_pending_changes(&e))
+    }
+
+    /// Get the timelock delay in seconds (0 means changes apply immediately)
+    pub fn get_timelock_delay(e: Env) -> Result<u64, KarmaError> {
+        Ok(storage::read_timelock_delay(&e))
+    }
+
+    // ADMIN FUNCTIONS //
+
+    /// Get the current owner, or nothing once ownership has been renounced
+    pub fn get_owner(e: Env) -> Result<Option<Address>, KarmaError> {
+        Ok(e.storage().instance().get(&OWNER))
+    }
+
+    /// Get the proposed owner that has not accepted yet, if any
+    pub fn get_pending_owner(e: Env) -> Result<Option<Address>, KarmaError> {
+        Ok(e.storage().instance().get(&PENDOWNER))
+    }
+
+    /// Propose a new owner, who takes over once they call `accept_ownership` (owner only).
+    /// A later proposal replaces an earlier one.
+    pub fn propose_owner(e: Env, new_owner: Address) -> Result<(), KarmaError> {
+        let owner: Address = e.storage().instance().get(&OWNER).ok_or(KarmaError::Unauthorized)?;
+        owner.require_auth();
+        
+        e.storage().instance().set(&PENDOWNER, &new_owner);
+        
+        // Keep contract settings alive
+        storage::extend_instance(&e);
+        
+        // Emit event
+        e.events().publish((symbol_short!("own_prop"), owner), new_owner);
+        
+        Ok(())
+    }
+
+    /// Accept a pending ownership proposal (proposed owner only)
+    pub fn accept_ownership(e: Env) -> Result<(), KarmaError> {
+        let new_owner: Address = e.storage().instance().get(&PENDOWNER).ok_or(KarmaError::Unauthorized)?;
+        new_owner.require_auth();
+        
+        let old_owner: Option<Address> = e.storage().instance().get(&OWNER);
+        e.storage().instance().set(&OWNER, &new_owner);
+        e.storage().instance().remove(&PENDOWNER);
+        
+        // Keep contract settings alive
+        storage::extend_instance(&e);
+        
+        // Emit event
+        e.events().publish((symbol_short!("own_acc"), new_owner), old_owner);
+        
+        Ok(())
+    }
+
+    /// Give up ownership for good, along with any pending proposal (owner only).
+    /// Roles granted before keep working, but none can be granted or revoked afterwards.
+    pub fn renounce_ownership(e: Env) -> Result<(), KarmaError> {
+        let owner: Address = e.storage().instance().get(&OWNER).ok_or(KarmaError::Unauthorized)?;
+        owner.require_auth();
+        
+        e.storage().instance().remove(&OWNER);
+        e.storage().instance().remove(&PENDOWNER);
+        
+        // Keep contract settings alive
+        storage::extend_instance(&e);
+        
+        // Emit event
+        e.events().publish((symbol_short!("own_renc"),), owner);
+        
+        Ok(())
+    }
+
+    /// Grant a role to an address (owner only)
+    pub fn grant_role(e: Env, admin: Address, role: Role, account: Address) -> Result<(), KarmaError> {
+        Self::require_owner(&e, &admin)?;
+        
+        storage::set_role(&e, role, &account, true);
+        
+        // Keep contract settings alive
+        storage::extend_instance(&e);
+        
+        // Emit event
+        e.events().publish((symbol_short!("role_add"), role), account);
+        
+        Ok(())
+    }
+
+    /// Revoke a role from an address (owner only). The owner's own roles cannot be revoked.
+    pub fn revoke_role(e: Env, admin: Address, role: Role, account: Address) -> Result<(), KarmaError> {
+        Self::require_owner(&e, &admin)?;
+        
+        storage::set_role(&e, role, &account, false);
+        
+        // Keep contract settings alive
+        storage::extend_instance(&e);
+        
+        // Emit event
+        e.events().publish((symbol_short!("role_del"), role), account);
+        
+        Ok(())
+    }
+
+    /// Check if an address holds a role
+    pub fn has_role(e: Env, role: Role, account: Address) -> Result<bool, KarmaError> {
+        Ok(roles::has_role(&e, role, &account))
+    }
+
+    /// Pause/unpause the contract (pauser only)
+    pub fn set_paused(e: Env, admin: Address, paused: bool) -> Result<(), KarmaError> {
+        roles::require(&e, Role::Pauser, &admin)?;
+        
+        e.storage().instance().set(&PAUSED, &paused);
+        
+        // Keep contract settings alive
+        storage::extend_instance(&e);
+        
+        // Emit events
+        e.events().publish((symbol_short!("pause"),), paused);
+        e.events().publish((symbol_short!("con_pause"),), KarmaEvent::ContractPaused(paused));
+        
+        Ok(())
+    }
+
+    /// Adjust the karma to XLM conversion rate (rate setter only).
+    /// Fails while the timelock is on; use `schedule_change` instead.
+    pub fn set_karma_rate(e: Env, admin: Address, rate: KarmaRate) -> Result<(), KarmaError> {
+        roles::require(&e, Role::RateSetter, &admin)?;
+        Self::require_no_timelock(&e)?;
+        
+        Self::apply_change(&e, ParamChange::KarmaRate(rate))
+    }
+
+    /// Accept a token for staking, or update its settings (admin only).
+    /// `weight` is the value of one unit relative to XLM in basis points and
+    /// `decimals` the token's decimals, so stakes can be compared across tokens.
+    pub fn add_stake_token(e: Env, admin: Address, token: Address, weight: u32, decimals: u32) -> Result<(), KarmaError> {
+        roles::require(&e, Role::Admin, &admin)?;
+        
+        if weight == 0 || decimals > 18 {
+            return Err(KarmaError::InvalidAmount);
+        }
+        
+        storage::write_stake_asset(&e, &token, Some(&StakeAsset { weight, decimals }));
+        storage::write_delisted_asset(&e, &token, None);
+        
+        // Keep contract settings alive
+        storage::extend_instance(&e);
+        
+        // Emit event
+        e.events().publish((symbol_short!("stk_add"), token), (weight, decimals));
+        
+        Ok(())
+    }
+
+    /// Stop accepting a token for staking (admin only). Existing stakes in it
+    /// can still be unstaked but no longer count towards the multiplier or
+    /// earn staking rewards.
+    pub fn remove_stake_token(e: Env, admin: Address, token: Address) -> Result<(), KarmaError> {
+        roles::require(&e, Role::Admin, &admin)?;
+        
+        // Stakers settle lazily, so remember how far the stake in it has earned
+        if let Some(asset) = storage::read_stake_asset(&e, &token) {
+            let per_share = rewards::update(&e);
+            storage::write_delisted_asset(&e, &token, Some((&asset, per_share)));
+        }
+        storage::write_stake_asset(&e, &token, None);
+        
+        // Keep contract settings alive
+        storage::extend_instance(&e);
+        
+        // Emit event
+        e.events().publish((symbol_short!("stk_del"),), token);
+        
+        Ok(())
+    }
+
+    /// Set the share of a stake slashed on a confirmed violation, in basis points (admin only)
+    pub fn set_slash_rate(e: Env, admin: Address, rate_bps: u32) -> Result<(), KarmaError> {
+        roles::require(&e, Role::Admin, &admin)?;
+        
+        if rate_bps == 0 || rate_bps > FULL_WEIGHT {
+            return Err(KarmaError::InvalidAmount);
+        }
+        
+        storage::write_slash_rate(&e, rate_bps);
+        
+        // Keep contract settings alive
+        storage::extend_instance(&e);
+        
+        // Emit event
+        e.events().publish((symbol_short!("slash_rt"),), rate_bps);
+        
+        Ok(())
+    }
+
+    /// Set the per-user and global redemption caps per epoch (admin only).
+    /// Changing the epoch length starts a fresh epoch for everyone.
+    pub fn set_redemption_limits(e: Env, admin: Address, limits: RedemptionLimits) -> Result<(), KarmaError> {
+        roles::require(&e, Role::Admin, &admin)?;
+        
+        if limits.epoch == 0 || limits.user_cap < 0 || limits.global_cap < 0 {
+            return Err(KarmaError::InvalidAmount);
+        }
         
-        let owner: Address = e.storage().instance().get(&OWNER).unwrap();
-        if admin != owner {
-            return Err(KarmaError::Unauthorized);
+        if limits.epoch != storage::read_redemption_limits(&e).epoch {
+            storage::write_redemption_start(&e, e.ledger().timestamp());
+        }
+        storage::write_redemption_limits(&e, &limits);
+        
+        // Keep contract settings alive
+        storage::extend_instance(&e);
+        
+        // Emit event
+        e.events().publish((symbol_short!("rdm_limit"),), limits);
+        
+        Ok(())
+    }
+
+    /// Set the KARMA token minted by `redeem_to_token` (admin only).
+    /// The token's admin must be this contract.
+    pub fn set_karma_token(e: Env, admin: Address, token: Address) -> Result<(), KarmaError> {
+        roles::require(&e, Role::Admin, &admin)?;
+        
+        e.storage().instance().set(&REPTOKEN, &token);
+        
+        // Keep contract settings alive
+        storage::extend_instance(&e);
+        
+        // Emit event
+        e.events().publish((symbol_short!("krm_token"),), token);
+        
+        Ok(())
+    }
+
+    /// Set the payout above which redemptions vest and the vesting duration (admin only).
+    /// Existing vesting redemptions keep their schedule.
+    pub fn set_vesting_config(e: Env, admin: Address, config: VestingConfig) -> Result<(), KarmaError> {
+        roles::require(&e, Role::Admin, &admin)?;
+        
+        if config.threshold < 0 || (config.threshold > 0 && config.duration == 0) {
+            return Err(KarmaError::InvalidAmount);
+        }
+        
+        storage::write_vesting_config(&e, &config);
+        
+        // Keep contract settings alive
+        storage::extend_instance(&e);
+        
+        // Emit event
+        e.events().publish((symbol_short!("vest_cfg"),), config);
+        
+        Ok(())
+    }
+
+    /// Move tokens out of the treasury, e.g. slashed stakes or unused redemption funds (admin only)
+    pub fn withdraw_treasury(e: Env, admin: Address, token: Address, to: Address, amount: i128) -> Result<(), KarmaError> {
+        roles::require(&e, Role::Admin, &admin)?;
+        
+        if amount <= 0 {
+            return Err(KarmaError::InvalidAmount);
+        }
+        
+        let balance = storage::read_treasury(&e, &token);
+        if balance < amount {
+            return Err(KarmaError::TreasuryInsufficient);
+        }
+        storage::write_treasury(&e, &token, balance - amount);
+        
+        let token_client = TokenClient::new(&e, &token);
+        token_client.transfer(&e.current_contract_address(), &to, &amount);
+        
+        // Keep contract settings alive
+        storage::extend_instance(&e);
+        
+        // Emit event
+        e.events().publish((symbol_short!("trs_wdraw"), token), (to, amount));
+        
+        Ok(())
+    }
+
+    /// Set the cooldown in seconds between two activities of a kind by the same user, 0 disables it (admin only)
+    pub fn set_cooldown(e: Env, admin: Address, kind: Symbol, seconds: u64) -> Result<(), KarmaError> {
+        roles::require(&e, Role::Admin, &admin)?;
+        
+        storage::write_cooldown(&e, &kind, seconds);
+        
+        // Keep contract settings alive
+        storage::extend_instance(&e);
+        
+        // Emit event
+        e.events().publish((symbol_short!("cooldown"), kind), seconds);
+        
+        Ok(())
+    }
+
+    /// Set the cap on karma a user can gain in a rolling 24h window, 0 disables it (admin only)
+    pub fn set_daily_cap(e: Env, admin: Address, cap: i32) -> Result<(), KarmaError> {
+        roles::require(&e, Role::Admin, &admin)?;
+        
+        if cap < 0 {
+            return Err(KarmaError::InvalidAmount);
+        }
+        
+        storage::write_daily_cap(&e, cap);
+        
+        // Keep contract settings alive
+        storage::extend_instance(&e);
+        
+        // Emit event
+        e.events().publish((symbol_short!("daily_cap"),), cap);
+        
+        Ok(())
+    }
+
+    /// Exempt a user from (or subject them to) rate limits (admin only)
+    pub fn set_rate_exempt(e: Env, admin: Address, user: Address, exempt: bool) -> Result<(), KarmaError> {
+        roles::require(&e, Role::Admin, &admin)?;
+        
+        storage::set_rate_exempt(&e, &user, exempt);
+        
+        // Keep contract settings alive
+        storage::extend_instance(&e);
+        
+        // Emit event
+        e.events().publish((symbol_short!("exempt"), user), exempt);
+        
+        Ok(())
+    }
+
+    /// Set the staking reward rate and karma boost (admin only).
+    /// Rewards accrued under the previous rate are kept.
+    pub fn set_stake_rewards(e: Env, admin: Address, config: RewardConfig) -> Result<(), KarmaError> {
+        roles::require(&e, Role::Admin, &admin)?;
+        
+        if config.rate < 0 || (config.max_karma_boost > 0 && config.boost_karma <= 0) {
+            return Err(KarmaError::InvalidAmount);
+        }
+        
+        // Settle the pool under the old rate first
+        rewards::update(&e);
+        storage::write_reward_config(&e, &config);
+        
+        // Keep contract settings alive
+        storage::extend_instance(&e);
+        
+        // Emit event
+        e.events().publish((symbol_short!("rwd_cfg"),), config);
+        
+        Ok(())
+    }
+
+    /// Replace the staking multiplier schedule (admin only).
+    /// Fails while the timelock is on; use `schedule_change` instead.
+    pub fn set_multiplier_schedule(e: Env, admin: Address, schedule: MultiplierSchedule) -> Result<(), KarmaError> {
+        roles::require(&e, Role::Admin, &admin)?;
+        Self::require_no_timelock(&e)?;
+        
+        Self::apply_change(&e, ParamChange::MultiplierSchedule(schedule))
+    }
+
+    /// Set how long unstaked tokens stay locked, in seconds (admin only).
+    /// Only applies to unstake requests made afterwards.
+    pub fn set_unbonding_period(e: Env, admin: Address, seconds: u64) -> Result<(), KarmaError> {
+        roles::require(&e, Role::Admin, &admin)?;
+        
+        storage::write_unbonding_period(&e, seconds);
+        
+        // Keep contract settings alive
+        storage::extend_instance(&e);
+        
+        // Emit event
+        e.events().publish((symbol_short!("unbond_p"),), seconds);
+        
+        Ok(())
+    }
+
+    /// Set the share of interaction karma credited to the content's author,
+    /// in basis points (0 = all to the actor, 10000 = all to the author) (admin only)
+    pub fn set_author_share(e: Env, admin: Address, share_bps: u32) -> Result<(), KarmaError> {
+        roles::require(&e, Role::Admin, &admin)?;
+        
+        if share_bps > 10_000 {
+            return Err(KarmaError::InvalidAmount);
+        }
+        
+        storage::write_author_share(&e, share_bps);
+        
+        // Keep contract settings alive
+        storage::extend_instance(&e);
+        
+        // Emit event
+        e.events().publish((symbol_short!("auth_shr"),), share_bps);
+        
+        Ok(())
+    }
+
+    /// Set the number of places kept on the leaderboard (admin only)
+    pub fn set_leaderboard_size(e: Env, admin: Address, size: u32) -> Result<(), KarmaError> {
+        roles::require(&e, Role::Admin, &admin)?;
+        
+        if size == 0 {
+            return Err(KarmaError::InvalidAmount);
         }
         
+        leaderboard::resize(&e, size);
+        
+        // Keep contract settings alive
+        storage::extend_instance(&e);
+        
+        // Emit event
+        e.events().publish((symbol_short!("lb_size"),), size);
+        
+        Ok(())
+    }
+
+    /// Set the karma half-life in seconds, 0 disables decay (admin only).
+    /// Decay is applied lazily; the new half-life only counts from the moment it is set.
+    pub fn set_decay_half_life(e: Env, admin: Address, half_life: u64) -> Result<(), KarmaError> {
+        roles::require(&e, Role::Admin, &admin)?;
+        
+        // Settle the running total under the old half-life first
+        storage::add_total_karma(&e, 0);
+        storage::write_half_life(&e, half_life);
+        
+        // Keep contract settings alive
+        storage::extend_instance(&e);
+        
+        // Emit event
+        e.events().publish((symbol_short!("decay"),), half_life);
+        
+        Ok(())
+    }
+
+    /// Define or update an activity kind in the catalogue (admin only).
+    /// Fails while the timelock is on; use `schedule_change` instead.
+    pub fn set_activity_kind(e: Env, admin: Address, kind: Symbol, base_karma: i32, enabled: bool) -> Result<(), KarmaError> {
+        roles::require(&e, Role::Admin, &admin)?;
+        Self::require_no_timelock(&e)?;
+        
+        Self::apply_change(&e, ParamChange::ActivityKind(kind, base_karma, enabled))
+    }
+
+    /// Turn on the timelock for the rate, multiplier schedule and activity kinds (admin only).
+    /// Once it is on, the delay itself can only be changed through `schedule_change`.
+    pub fn set_timelock_delay(e: Env, admin: Address, seconds: u64) -> Result<(), KarmaError> {
+        roles::require(&e, Role::Admin, &admin)?;
+        Self::require_no_timelock(&e)?;
+        
+        Self::apply_change(&e, ParamChange::TimelockDelay(seconds))
+    }
+
+    /// Schedule a change that anyone can execute once the timelock delay has passed.
+    /// Rate changes need the rate setter role, everything else the admin role.
+    pub fn schedule_change(e: Env, admin: Address, change: ParamChange) -> Result<PendingChange, KarmaError> {
+        roles::require(&e, Self::change_role(&change), &admin)?;
+        Self::validate_change(&change)?;
+        
+        let pending = timelock::schedule(&e, change);
+        
+        // Keep contract settings alive
+        storage::extend_instance(&e);
+        
+        // Emit event
+        e.events().publish((symbol_short!("tl_sched"), pending.id), (pending.change.clone(), pending.eta));
+        
+        Ok(pending)
+    }
+
+    /// Cancel a scheduled change before it is executed (same role as scheduling it)
+    pub fn cancel_change(e: Env, admin: Address, id: u32) -> Result<(), KarmaError> {
+        let pending = timelock::get(&e, id)?;
+        roles::require(&e, Self::change_role(&pending.change), &admin)?;
+        
+        timelock::remove(&e, id);
+        
+        // Keep contract settings alive
+        storage::extend_instance(&e);
+        
+        // Emit event
+        e.events().publish((symbol_short!("tl_cancel"), id), pending.change);
+        
+        Ok(())
+    }
+
+    /// Register the ed25519 public key that signs activity attestations (admin only)
+    pub fn set_attestor(e: Env, admin: Address, public_key: BytesN<32>) -> Result<(), KarmaError> {
+        roles::require(&e, Role::Admin, &admin)?;
+        
+        e.storage().instance().set(&ATTESTOR, &public_key);
+        
+        // Keep contract settings alive
+        storage::extend_instance(&e);
+        
+        // Emit event
+        e.events().publish((symbol_short!("attestor"),), public_key);
+        
+        Ok(())
+    }
+
+    /// Reset a user's karma and stake (admin only, for testing).
+    /// Staked and unbonding tokens are paid back to the user.
+    pub fn reset_user(e: Env, admin: Address, user: Address) -> Result<(), KarmaError> {
+        roles::require(&e, Role::Admin, &admin)?;
+        
+        Self::sync_reward_shares(&e, &user); // Settle rewards on the shares held until now
+        
         // Reset user's karma
-        let mut users: Map<Address, UserData> = e.storage().instance().get(&USERS).unwrap_or_else(|| Map::new(&e));
-        if users.contains_key(user.clone()) {
-            let mut user_data = users.get(user.clone()).unwrap();
+        if let Some(mut user_data) = storage::read_decayed_user(&e, &user) {
+            storage::add_total_karma(&e, -user_data.karma_points);
             user_data.karma_points = 0;
-            users.set(user.clone(), user_data);
-            e.storage().instance().set(&USERS, &users);
+            storage::write_user(&e, &user, &user_data);
+            leaderboard::update(&e, &user, &user_data);
+        }
+        
+        // Return the user's stake and anything still unbonding
+        let mut refunded = Map::new(&e);
+        for (token, amount) in storage::read_stakes(&e, &user).iter() {
+            refunded.set(token.clone(), refunded.get(token).unwrap_or(0) + amount);
+        }
+        for entry in storage::read_unbonding(&e, &user).iter() {
+            refunded.set(entry.token.clone(), refunded.get(entry.token).unwrap_or(0) + entry.amount);
         }
+        storage::write_stakes(&e, &user, &Map::new(&e));
+        storage::write_unbonding(&e, &user, &Vec::new(&e));
+        Self::sync_reward_shares(&e, &user);
         
-        // Reset user's stake
-        let mut stakes: Map<Address, i128> = e.storage().instance().get(&STAKES).unwrap_or_else(|| Map::new(&e));
-        stakes.set(user.clone(), 0);
-        e.storage().instance().set(&STAKES, &stakes);
+        for (token, amount) in refunded.iter() {
+            let token_client = TokenClient::new(&e, &token);
+            token_client.transfer(&e.current_contract_address(), &user, &amount);
+        }
+        
+        // Keep contract settings and the user's entries alive
+        storage::extend_instance(&e);
+        storage::extend_user(&e, &user);
         
         // Emit event
         e.events().publish((symbol_short!("reset"),), user);
@@ -455,56 +1792,283 @@ impl KarmaEngineContract {
         Ok(())
     }
 
-    /// Internal function to record any activity and update karma
-    fn record_activity(e: Env, user: Address, activity_type: ActivityType, base_karma: i32) -> Result<i32, KarmaError> {
+    /// Drain up to `limit` entries of the legacy instance-storage maps into
+    /// keyed persistent storage (admin only). Returns the number of legacy
+    /// entries still waiting to be migrated; call again until it reaches 0.
+    pub fn migrate_storage(e: Env, admin: Address, limit: u32) -> Result<u32, KarmaError> {
+        roles::require(&e, Role::Admin, &admin)?;
+        
+        let remaining = storage::migrate_legacy(&e, limit);
+        
+        // Keep contract settings alive
+        storage::extend_instance(&e);
+        
+        // Emit event
+        e.events().publish((symbol_short!("migrate"),), remaining);
+        
+        Ok(remaining)
+    }
+
+    /// Extend the TTL of the contract instance and of every listed user (admin only)
+    pub fn bump_all(e: Env, admin: Address, users: Vec<Address>) -> Result<(), KarmaError> {
+        roles::require(&e, Role::Admin, &admin)?;
+        
+        storage::extend_instance(&e);
+        for user in users.iter() {
+            storage::extend_user(&e, &user);
+        }
+        
+        // Emit event
+        e.events().publish((symbol_short!("bump"),), users.len());
+        
+        Ok(())
+    }
+
+    /// Internal function to read the karma to XLM conversion rate
+    fn karma_rate(e: &Env) -> KarmaRate {
+        e.storage().instance().get(&KARMARATE).unwrap_or(KarmaRate { karma: DEFAULT_RATE_KARMA, stroops: DEFAULT_RATE_STROOPS })
+    }
+
+    /// Internal function to convert karma at `rate`, returning the karma used
+    /// (whole multiples of `rate.karma`) and the XLM it is worth in stroops
+    fn convert_karma(rate: &KarmaRate, karma_amount: i32) -> Result<(i32, i128), KarmaError> {
+        let units = karma_amount as i128 / rate.karma as i128;
+        let xlm_amount = units.checked_mul(rate.stroops).ok_or(KarmaError::InvalidAmount)?;
+        Ok(((units * rate.karma as i128) as i32, xlm_amount))
+    }
+
+    /// Internal function to compute how much of a vesting redemption has vested at `now`
+    fn vested(entry: &VestingEntry, now: u64) -> i128 {
+        if now >= entry.end {
+            return entry.amount;
+        }
+        let elapsed = now.saturating_sub(entry.start) as i128;
+        entry.amount * elapsed / (entry.end - entry.start) as i128
+    }
+
+    /// Internal function to value a stake in XLM terms (7 decimals)
+    fn stake_value(amount: i128, asset: &StakeAsset) -> i128 {
+        let weighted = amount * asset.weight as i128 / FULL_WEIGHT as i128;
+        if asset.decimals >= STAKE_VALUE_DECIMALS {
+            weighted / 10i128.pow(asset.decimals - STAKE_VALUE_DECIMALS)
+        } else {
+            weighted * 10i128.pow(STAKE_VALUE_DECIMALS - asset.decimals)
+        }
+    }
+
+    /// Internal function to settle a staker and move them to their current reward shares.
+    /// Called before a write to settle what the old shares earned, and after it.
+    fn sync_reward_shares(e: &Env, user: &Address) {
+        rewards::set_shares(e, user, &Self::reward_shares(e, user));
+    }
+
+    /// Internal function to compute a staker's reward shares: the weighted value
+    /// of their stake, boosted by up to `max_karma_boost` for their decayed karma.
+    /// Stake in delisted tokens is valued at the settings they were delisted with.
+    fn reward_shares(e: &Env, user: &Address) -> rewards::Shares {
+        let config = storage::read_reward_config(e);
+        
+        let mut boost = 0;
+        if config.max_karma_boost > 0 {
+            let karma = storage::read_decayed_user(e, user).map(|data| data.karma_points).unwrap_or(0);
+            let karma = karma.clamp(0, config.boost_karma);
+            boost = config.max_karma_boost as i128 * karma as i128 / config.boost_karma as i128;
+        }
+        let boosted = |value: i128| value * (FULL_WEIGHT as i128 + boost) / FULL_WEIGHT as i128;
+        
+        let mut delisted = Vec::new(e);
+        for (token, amount) in storage::read_stakes(e, user).iter() {
+            if storage::read_stake_asset(e, &token).is_none() {
+                if let Some((asset, per_share)) = storage::read_delisted_asset(e, &token) {
+                    delisted.push_back((per_share, boosted(Self::stake_value(amount, &asset))));
+                }
+            }
+        }
+        
+        rewards::Shares { listed: boosted(Self::stake_total(e, user)), delisted }
+    }
+
+    /// Internal function to sum the value of a user's stake in accepted tokens
+    fn stake_total(e: &Env, user: &Address) -> i128 {
+        let mut total = 0;
+        for (token, amount) in storage::read_stakes(e, user).iter() {
+            if let Some(asset) = storage::read_stake_asset(e, &token) {
+                total += Self::stake_value(amount, &asset);
+            }
+        }
+        total
+    }
+
+    /// Internal function to look up the base karma of an activity type
+    fn base_karma(e: &Env, activity_type: &ActivityType) -> Result<i32, KarmaError> {
+        let kind = Self::get_activity_kind(e.clone(), activity_type.symbol())?;
+        if !kind.enabled {
+            return Err(KarmaError::ActivityDisabled);
+        }
+        Ok(kind.base_karma)
+    }
+
+    /// Internal function to refuse direct changes to timelocked settings
+    fn require_no_timelock(e: &Env) -> Result<(), KarmaError> {
+        if storage::read_timelock_delay(e) > 0 {
+            return Err(KarmaError::Timelocked);
+        }
+        Ok(())
+    }
+
+    /// Internal function to look up the role allowed to make a change
+    fn change_role(change: &ParamChange) -> Role {
+        match change {
+            ParamChange::KarmaRate(_) => Role::RateSetter,
+            _ => Role::Admin,
+        }
+    }
+
+    /// Internal function to check a change before it is scheduled or applied
+    fn validate_change(change: &ParamChange) -> Result<(), KarmaError> {
+        match change {
+            ParamChange::KarmaRate(rate) if rate.karma == 0 || rate.stroops <= 0 => Err(KarmaError::InvalidAmount),
+            ParamChange::MultiplierSchedule(schedule) if !multiplier::is_valid(schedule) => Err(KarmaError::InvalidSchedule),
+            // Slashes are only ever recorded by `slash`, never through the catalogue
+            ParamChange::ActivityKind(kind, ..) if ActivityType::from_symbol(kind.clone()) == ActivityType::Slash => {
+                Err(KarmaError::UnknownActivity)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Internal function to apply a change to a timelocked setting
+    fn apply_change(e: &Env, change: ParamChange) -> Result<(), KarmaError> {
+        Self::validate_change(&change)?;
+        
+        match change {
+            ParamChange::KarmaRate(rate) => {
+                e.storage().instance().set(&KARMARATE, &rate);
+                e.events().publish((symbol_short!("rate"),), rate);
+            }
+            ParamChange::MultiplierSchedule(schedule) => {
+                storage::write_multiplier_schedule(e, &schedule);
+                e.events().publish((symbol_short!("mult_sch"),), schedule);
+            }
+            ParamChange::ActivityKind(kind, base_karma, enabled) => {
+                storage::write_activity_kind(e, &kind, &ActivityKind { base_karma, enabled });
+                e.events().publish((symbol_short!("act_kind"), kind), (base_karma, enabled));
+            }
+            ParamChange::TimelockDelay(seconds) => {
+                storage::write_timelock_delay(e, seconds);
+                e.events().publish((symbol_short!("tl_delay"),), seconds);
+            }
+        }
+        
+        // Keep contract settings alive
+        storage::extend_instance(e);
+        
+        Ok(())
+    }
+
+    /// Internal function to check that the caller is the current owner
+    fn require_owner(e: &Env, admin: &Address) -> Result<(), KarmaError> {
+        admin.require_auth();
+        
+        let owner: Option<Address> = e.storage().instance().get(&OWNER);
+        if owner.as_ref() != Some(admin) {
+            return Err(KarmaError::Unauthorized);
+        }
+        
+        Ok(())
+    }
+
+    /// Internal function to record any activity and update karma.
+    /// Positive karma for acting on someone else's content is split with its
+    /// author according to the author share; each party gets their own multiplier.
+    fn record_activity(e: Env, user: Address, activity_type: ActivityType, base_karma: i32, content_id: BytesN<32>, author: Option<Address>) -> Result<i32, KarmaError> {
         // Check if user is registered
-        let mut users: Map<Address, UserData> = e.storage().instance().get(&USERS).unwrap_or_else(|| Map::new(&e));
+        let user_data = storage::read_decayed_user(&e, &user).ok_or(KarmaError::NotRegistered)?;
         
-        if !users.contains_key(user.clone()) {
-            return Err(KarmaError::NotRegistered);
+        if activity_type.is_interaction() && author.is_none() {
+            return Err(KarmaError::MissingAuthor);
+        }
+        if author.as_ref() == Some(&user) {
+            return Err(KarmaError::SelfInteraction);
+        }
+        
+        // Each user can only record a kind once per piece of content
+        let kind = activity_type.symbol();
+        if storage::is_seen(&e, &user, &kind, &content_id) {
+            return Err(KarmaError::DuplicateActivity);
+        }
+        
+        // Work out the author's cut of the base karma
+        let author_share = storage::read_author_share(&e);
+        let mut author_credit = None;
+        let mut actor_base = base_karma;
+        if let Some(author) = author.clone() {
+            if author_share > 0 && base_karma > 0 {
+                let author_data = storage::read_decayed_user(&e, &author).ok_or(KarmaError::NotRegistered)?;
+                let author_base = (base_karma as i64 * author_share as i64 / 10_000) as i32;
+                actor_base -= author_base;
+                author_credit = Some((author, author_data, author_base));
+            }
         }
         
         // Calculate karma with multiplier
         let multiplier = Self::get_multiplier(e.clone(), user.clone())?;
         // Apply multiplier (multiplier is stored with 1 decimal place, so divide by 10)
-        let karma_change = (base_karma as i64 * multiplier as i64 / 10) as i32;
+        let karma_change = (actor_base as i64 * multiplier as i64 / 10) as i32;
         
-        // Update user's karma
-        let mut user_data = users.get(user.clone()).unwrap();
-        let karma_points = user_data.karma_points + karma_change;
-        user_data.karma_points = karma_points;
-        users.set(user.clone(), user_data);
-        e.storage().instance().set(&USERS, &users);
+        // Enforce cooldowns and the daily cap
+        ratelimit::check(&e, &user, &activity_type, karma_change)?;
         
-        // Record activity with limit on history size
-        let mut activities_store: Map<Address, Vec<ActivityRecord>> = e.storage().instance().get(&ACTIVITY).unwrap_or_else(|| Map::new(&e));
-        let mut user_activities = activities_store.get(user.clone()).unwrap_or_else(|| Vec::new(&e));
+        storage::mark_seen(&e, &user, &kind, &content_id);
         
+        let timestamp = e.ledger().timestamp();
         let activity_record = ActivityRecord {
             activity_type,
+            content_id,
+            author,
             karma_change,
-            timestamp: e.ledger().timestamp(),
+            timestamp,
         };
         
-        // Add new activity to the front of the list (newest first)
-        user_activities.push_front(activity_record);
-        
-        // Limit the history to MAX_ACTIVITY_HISTORY entries
-        if user_activities.len() > MAX_ACTIVITY_HISTORY {
-            // Remove the oldest entries (from the back)
-            while user_activities.len() > MAX_ACTIVITY_HISTORY {
-                user_activities.pop_back();
-            }
+        // Credit the author with their cut under their own multiplier
+        if let Some((author, author_data, author_base)) = author_credit {
+            let multiplier = Self::get_multiplier(e.clone(), author.clone())?;
+            let author_change = (author_base as i64 * multiplier as i64 / 10) as i32;
+            // The author's daily cap still applies, anything over it is dropped
+            let author_change = ratelimit::clamp(&e, &author, author_change);
+            // The author's copy names who interacted with their content
+            let author_record = ActivityRecord { author: Some(user.clone()), karma_change: author_change, ..activity_record.clone() };
+            Self::apply_karma(&e, &author, author_data, author_record);
         }
         
-        activities_store.set(user.clone(), user_activities);
-        e.storage().instance().set(&ACTIVITY, &activities_store);
+        Self::apply_karma(&e, &user, user_data, activity_record);
+        
+        Ok(karma_change)
+    }
+
+    /// Internal function to add an activity's karma change to a user and log it
+    fn apply_karma(e: &Env, user: &Address, mut user_data: UserData, activity_record: ActivityRecord) {
+        Self::sync_reward_shares(e, user); // Settle rewards on the shares held until now
+        
+        // Update user's karma
+        let karma_change = activity_record.karma_change;
+        let karma_points = user_data.karma_points + karma_change;
+        user_data.karma_points = karma_points;
+        storage::write_user(e, user, &user_data);
+        storage::add_total_karma(e, karma_change);
+        leaderboard::update(e, user, &user_data);
+        Self::sync_reward_shares(e, user);
+        
+        // Record activity with limit on history size
+        storage::push_activity(e, user, activity_record);
+        
+        // Keep contract settings and the user's entries alive
+        storage::extend_instance(e);
+        storage::extend_user(e, user);
         
         // Emit events
         e.events().publish((symbol_short!("karma"),), (user.clone(), karma_change));
-        e.events().publish((symbol_short!("karma_upd"),), KarmaEvent::KarmaUpdated(user, karma_points));
-        
-        Ok(karma_change)
+        e.events().publish((symbol_short!("karma_upd"),), KarmaEvent::KarmaUpdated(user.clone(), karma_points));
     }
 }
 
@@ -0,0 +1,59 @@
+use crate::{MultiplierSchedule, ScalingMode};
+
+/// Multiplier (with 1 decimal place) for a stake worth `value` under `schedule`.
+/// The highest tier whose threshold is reached applies; above the top tier the
+/// scaling mode adds `step` per doubling (log2) or per whole square root of the
+/// stake relative to the top threshold, up to `max_multiplier`.
+pub fn multiplier(schedule: &MultiplierSchedule, value: i128) -> u32 {
+    let mut multiplier = 0;
+    let mut reached = 0;
+    for tier in schedule.tiers.iter() {
+        if value < tier.threshold {
+            break;
+        }
+        multiplier = tier.multiplier;
+        reached += 1;
+    }
+
+    // Scaling only applies once the top tier is reached
+    if reached == schedule.tiers.len() {
+        let top = schedule.tiers.last().unwrap().threshold;
+        let steps = match schedule.scaling {
+            ScalingMode::Flat => 0,
+            ScalingMode::Log2 => (value / top).ilog2(),
+            ScalingMode::Sqrt => ((value / top).isqrt() - 1).min(u32::MAX as i128) as u32,
+        };
+        let bonus = (steps as u64 * schedule.step as u64).min(u32::MAX as u64) as u32;
+        multiplier = multiplier.saturating_add(bonus);
+    }
+
+    multiplier.min(schedule.max_multiplier)
+}
+
+/// Check that a schedule starts at 0, has strictly increasing thresholds and
+/// non-decreasing multipliers, and that scaling has a positive top threshold to scale from
+pub fn is_valid(schedule: &MultiplierSchedule) -> bool {
+    let first = match schedule.tiers.first() {
+        Some(first) => first,
+        None => return false,
+    };
+    if first.threshold != 0 || first.multiplier == 0 {
+        return false;
+    }
+
+    let mut previous = first;
+    for tier in schedule.tiers.iter().skip(1) {
+        if tier.threshold <= previous.threshold || tier.multiplier < previous.multiplier {
+            return false;
+        }
+        previous = tier;
+    }
+    if previous.multiplier > schedule.max_multiplier {
+        return false;
+    }
+
+    match schedule.scaling {
+        ScalingMode::Flat => true,
+        ScalingMode::Log2 | ScalingMode::Sqrt => previous.threshold > 0 && schedule.step > 0,
+    }
+}
@@ -0,0 +1,107 @@
+use soroban_sdk::{contracttype, Address, Env, Vec};
+
+use crate::storage;
+use crate::{ActivityType, KarmaError};
+
+// Length of the rolling window the daily cap applies to
+const WINDOW_SECONDS: u64 = 24 * 60 * 60;
+
+// Granularity of the rolling window
+const BUCKET_SECONDS: u64 = 60 * 60;
+
+// Karma gained by a user during one hour of the rolling window
+#[contracttype]
+#[derive(Clone)]
+pub struct GainBucket {
+    pub hour: u64,
+    pub gained: i32,
+}
+
+/// Enforce the activity cooldown and the rolling 24h karma cap for `user`,
+/// then record the activity against them. Nothing is recorded unless both
+/// checks pass. Exempt users are not limited.
+pub fn check(e: &Env, user: &Address, activity_type: &ActivityType, karma_change: i32) -> Result<(), KarmaError> {
+    if storage::is_rate_exempt(e, user) {
+        return Ok(());
+    }
+
+    let now = e.ledger().timestamp();
+    let kind = activity_type.symbol();
+
+    // Per-kind cooldown between two activities of the same user
+    let cooldown = storage::read_cooldown(e, &kind);
+    if cooldown > 0 {
+        if let Some(last) = storage::read_last_activity(e, user, &kind) {
+            if now < last + cooldown {
+                return Err(KarmaError::RateLimited);
+            }
+        }
+    }
+
+    // Rolling 24h cap on karma gained, penalties are never limited
+    let cap = storage::read_daily_cap(e);
+    let mut gains = None;
+    if cap > 0 && karma_change > 0 {
+        let (buckets, gained) = recent_gains(e, user, now);
+        if gained + karma_change > cap {
+            return Err(KarmaError::RateLimited);
+        }
+        gains = Some(add_gain(buckets, now, karma_change));
+    }
+
+    // Both checks passed, record the activity
+    if cooldown > 0 {
+        storage::write_last_activity(e, user, &kind, now, cooldown);
+    }
+    if let Some(buckets) = gains {
+        storage::write_gains(e, user, &buckets, WINDOW_SECONDS);
+    }
+
+    Ok(())
+}
+
+/// Clamp karma credited to `user` for someone else's activity (an author's
+/// cut) to what is left of their rolling 24h cap, and record it against them.
+/// Cooldowns do not apply. Exempt users are not limited.
+pub fn clamp(e: &Env, user: &Address, karma_change: i32) -> i32 {
+    let cap = storage::read_daily_cap(e);
+    if cap == 0 || karma_change <= 0 || storage::is_rate_exempt(e, user) {
+        return karma_change;
+    }
+
+    let now = e.ledger().timestamp();
+    let (buckets, gained) = recent_gains(e, user, now);
+    let allowed = karma_change.min(cap - gained).max(0);
+    if allowed > 0 {
+        storage::write_gains(e, user, &add_gain(buckets, now, allowed), WINDOW_SECONDS);
+    }
+    allowed
+}
+
+// Buckets still inside the rolling window and the karma gained in them
+fn recent_gains(e: &Env, user: &Address, now: u64) -> (Vec<GainBucket>, i32) {
+    let oldest = (now.saturating_sub(WINDOW_SECONDS)) / BUCKET_SECONDS;
+
+    let mut buckets = Vec::new(e);
+    let mut gained: i32 = 0;
+    for bucket in storage::read_gains(e, user).iter() {
+        if bucket.hour > oldest {
+            gained += bucket.gained;
+            buckets.push_back(bucket);
+        }
+    }
+    (buckets, gained)
+}
+
+// Add a gain to the bucket of the current hour
+fn add_gain(mut buckets: Vec<GainBucket>, now: u64, karma_change: i32) -> Vec<GainBucket> {
+    let hour = now / BUCKET_SECONDS;
+    match buckets.last() {
+        Some(mut current) if current.hour == hour => {
+            current.gained += karma_change;
+            buckets.set(buckets.len() - 1, current);
+        }
+        _ => buckets.push_back(GainBucket { hour, gained: karma_change }),
+    }
+    buckets
+}
@@ -0,0 +1,76 @@
+use soroban_sdk::{contracttype, symbol_short, Address, Env};
+
+use crate::storage;
+use crate::KarmaError;
+
+// XLM redeemed during one epoch
+#[contracttype]
+#[derive(Clone, Default)]
+pub struct EpochUsage {
+    pub epoch: u64, // Ledger timestamp the epoch started at
+    pub redeemed: i128,
+}
+
+/// Start of the epoch the current ledger time falls in. Epochs are counted
+/// from the last change of the epoch length, so usage recorded under an
+/// earlier length never matches a current epoch.
+fn current_epoch(e: &Env) -> u64 {
+    let start = storage::read_redemption_start(e);
+    let length = storage::read_redemption_limits(e).epoch;
+    start + (e.ledger().timestamp() - start) / length * length
+}
+
+/// Amount redeemed so far in the current epoch, 0 once a new epoch starts
+fn redeemed(usage: Option<EpochUsage>, epoch: u64) -> i128 {
+    match usage {
+        Some(usage) if usage.epoch == epoch => usage.redeemed,
+        _ => 0,
+    }
+}
+
+/// Room left under a cap, `None` when the cap is disabled
+fn room(cap: i128, redeemed: i128) -> Option<i128> {
+    if cap > 0 {
+        Some((cap - redeemed).max(0))
+    } else {
+        None
+    }
+}
+
+/// XLM `user` can still redeem this epoch under the per-user and global caps,
+/// `None` when neither cap is set
+pub fn allowance(e: &Env, user: &Address) -> Option<i128> {
+    let limits = storage::read_redemption_limits(e);
+    let epoch = current_epoch(e);
+    let user_room = room(limits.user_cap, redeemed(storage::read_user_redeemed(e, user), epoch));
+    let global_room = room(limits.global_cap, redeemed(storage::read_global_redeemed(e), epoch));
+    match (user_room, global_room) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+/// Enforce the per-user and global caps for a payout of `amount` and record it.
+/// Emits `cap_hit` once the global cap has no room left for another `unit`.
+pub fn check(e: &Env, user: &Address, amount: i128, unit: i128) -> Result<(), KarmaError> {
+    let limits = storage::read_redemption_limits(e);
+    let epoch = current_epoch(e);
+
+    let user_redeemed = redeemed(storage::read_user_redeemed(e, user), epoch) + amount;
+    let global_redeemed = redeemed(storage::read_global_redeemed(e), epoch) + amount;
+    if (limits.user_cap > 0 && user_redeemed > limits.user_cap)
+        || (limits.global_cap > 0 && global_redeemed > limits.global_cap)
+    {
+        return Err(KarmaError::RedemptionLimited);
+    }
+
+    let user_usage = EpochUsage { epoch, redeemed: user_redeemed };
+    storage::write_user_redeemed(e, user, &user_usage, limits.epoch);
+    storage::write_global_redeemed(e, &EpochUsage { epoch, redeemed: global_redeemed });
+
+    if limits.global_cap > 0 && limits.global_cap - global_redeemed < unit {
+        e.events().publish((symbol_short!("cap_hit"),), (epoch, global_redeemed));
+    }
+
+    Ok(())
+}
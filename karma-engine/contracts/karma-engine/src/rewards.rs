@@ -0,0 +1,120 @@
+use soroban_sdk::{contracttype, Address, Env, Vec};
+
+use crate::storage;
+
+// Fixed-point scale of the reward-per-share accumulator
+const PRECISION: i128 = 1_000_000_000_000_000_000;
+
+// Global state of the staking reward pool
+#[contracttype]
+#[derive(Clone, Default)]
+pub struct RewardPool {
+    pub per_share: i128,     // Rewards paid per share since the start, scaled by PRECISION
+    pub updated_at: u64,     // Ledger timestamp the accumulator was last brought up to date
+    pub undistributed: i128, // Funded rewards not yet streamed to stakers
+    pub total_shares: i128,
+}
+
+// A staker's position in the reward pool
+#[contracttype]
+#[derive(Clone, Default)]
+pub struct StakerRewards {
+    pub shares: i128,
+    pub paid_per_share: i128, // Accumulator value the staker was last settled at
+    pub pending: i128,        // Settled rewards not yet claimed
+}
+
+// A staker's current shares, and for each delisted token they still hold
+// the accumulator value it was delisted at with the shares its stake had
+pub struct Shares {
+    pub listed: i128,
+    pub delisted: Vec<(i128, i128)>,
+}
+
+/// Stream rewards to stakers up to the current ledger time at `rate` per
+/// second, never more than has been funded. Nothing is streamed while there
+/// are no shares, so those rewards stay in the pool.
+fn accrue(e: &Env, pool: &mut RewardPool) {
+    let now = e.ledger().timestamp();
+    if pool.total_shares > 0 {
+        let elapsed = now.saturating_sub(pool.updated_at) as i128;
+        let streamed = (storage::read_reward_config(e).rate * elapsed).min(pool.undistributed);
+        pool.per_share += streamed * PRECISION / pool.total_shares;
+        pool.undistributed -= streamed;
+    }
+    pool.updated_at = now;
+}
+
+/// Bring a staker's pending rewards up to date with the pool and move them
+/// to `shares`. Karma decay only shows up here, so since the last settlement
+/// the staker earns on the lower of their recorded and current shares, plus
+/// the stake in delisted tokens up to when each was delisted. Whatever else
+/// their recorded shares earned goes back to the pool.
+fn settle(pool: &mut RewardPool, staker: &mut StakerRewards, shares: &Shares) {
+    let growth = pool.per_share - staker.paid_per_share;
+    let mut earned = staker.shares.min(shares.listed) * growth;
+    for (delisted_at, delisted_shares) in shares.delisted.iter() {
+        let until = delisted_at.clamp(staker.paid_per_share, pool.per_share);
+        earned += delisted_shares * (until - staker.paid_per_share);
+    }
+    let earned = earned.min(staker.shares * growth);
+    staker.pending += earned / PRECISION;
+    pool.undistributed += (staker.shares * growth - earned) / PRECISION;
+    pool.total_shares += shares.listed - staker.shares;
+    staker.shares = shares.listed;
+    staker.paid_per_share = pool.per_share;
+}
+
+/// Bring the pool up to date, e.g. before its rate changes, and return the
+/// accumulator value
+pub fn update(e: &Env) -> i128 {
+    let mut pool = storage::read_reward_pool(e);
+    accrue(e, &mut pool);
+    storage::write_reward_pool(e, &pool);
+    pool.per_share
+}
+
+/// Add funded rewards to the pool
+pub fn fund(e: &Env, amount: i128) {
+    let mut pool = storage::read_reward_pool(e);
+    accrue(e, &mut pool);
+    pool.undistributed += amount;
+    storage::write_reward_pool(e, &pool);
+}
+
+/// Settle `user` and change their shares from now on
+pub fn set_shares(e: &Env, user: &Address, shares: &Shares) {
+    let mut staker = storage::read_staker_rewards(e, user);
+    if staker.shares == 0 && shares.listed == 0 {
+        return;
+    }
+
+    let mut pool = storage::read_reward_pool(e);
+    accrue(e, &mut pool);
+    settle(&mut pool, &mut staker, shares);
+    storage::write_reward_pool(e, &pool);
+    storage::write_staker_rewards(e, user, &staker);
+}
+
+/// Settle `user` at their current `shares` and take their pending rewards
+/// out of the pool
+pub fn take(e: &Env, user: &Address, shares: &Shares) -> i128 {
+    let mut pool = storage::read_reward_pool(e);
+    accrue(e, &mut pool);
+    let mut staker = storage::read_staker_rewards(e, user);
+    settle(&mut pool, &mut staker, shares);
+    let amount = staker.pending;
+    staker.pending = 0;
+    storage::write_reward_pool(e, &pool);
+    storage::write_staker_rewards(e, user, &staker);
+    amount
+}
+
+/// Rewards `user` could claim right now, given their current `shares`
+pub fn pending(e: &Env, user: &Address, shares: &Shares) -> i128 {
+    let mut pool = storage::read_reward_pool(e);
+    accrue(e, &mut pool);
+    let mut staker = storage::read_staker_rewards(e, user);
+    settle(&mut pool, &mut staker, shares);
+    staker.pending
+}
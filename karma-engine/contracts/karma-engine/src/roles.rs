@@ -0,0 +1,21 @@
+use soroban_sdk::{Address, Env};
+
+use crate::storage;
+use crate::{KarmaError, Role, OWNER};
+
+/// Check whether `account` holds `role`; the owner holds every role
+pub fn has_role(e: &Env, role: Role, account: &Address) -> bool {
+    let owner: Option<Address> = e.storage().instance().get(&OWNER);
+    owner.as_ref() == Some(account) || storage::has_role(e, role, account)
+}
+
+/// Check that the caller signed and holds `role`
+pub fn require(e: &Env, role: Role, account: &Address) -> Result<(), KarmaError> {
+    account.require_auth();
+
+    if !has_role(e, role, account) {
+        return Err(KarmaError::Unauthorized);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,779 @@
+use soroban_sdk::{contracttype, symbol_short, Address, BytesN, Env, Map, Symbol, Vec};
+
+use crate::decay::decay;
+use crate::leaderboard;
+use crate::ratelimit::GainBucket;
+use crate::redemption::EpochUsage;
+use crate::rewards::{RewardPool, StakerRewards};
+use crate::{ActivityKind, ActivityRecord, ActivityType, KarmaEngineContract, LeaderboardEntry, MultiplierSchedule, PendingChange, RedemptionLimits, RewardConfig, Role, StakeAsset, UnbondingEntry, UserData, VestingConfig, VestingEntry, MAX_ACTIVITY_HISTORY};
+
+// TTL policy, in ledgers (~5s each)
+const DAY_IN_LEDGERS: u32 = 17280;
+pub(crate) const INSTANCE_BUMP_AMOUNT: u32 = 30 * DAY_IN_LEDGERS;
+pub(crate) const INSTANCE_LIFETIME_THRESHOLD: u32 = INSTANCE_BUMP_AMOUNT - DAY_IN_LEDGERS;
+pub(crate) const USER_BUMP_AMOUNT: u32 = 90 * DAY_IN_LEDGERS;
+pub(crate) const USER_LIFETIME_THRESHOLD: u32 = USER_BUMP_AMOUNT - DAY_IN_LEDGERS;
+
+// Running sum of every user's karma and when it was last updated, kept in instance storage
+const TOTALK: Symbol = symbol_short!("TOTALK");
+const TOTALT: Symbol = symbol_short!("TOTALT");
+
+// Number of places kept on the leaderboard
+const LBSIZE: Symbol = symbol_short!("LBSIZE");
+const DEFAULT_LEADERBOARD_SIZE: u32 = 100;
+
+// Cap on karma gained per user in a rolling 24h window, 0 disables it
+const DAILYCAP: Symbol = symbol_short!("DAILYCAP");
+
+// Staking multiplier schedule, unset until the owner replaces the default
+const MULTSCHED: Symbol = symbol_short!("MULTSCHED");
+
+// Share of a stake slashed on a confirmed violation, in basis points
+const SLASHRT: Symbol = symbol_short!("SLASHRT");
+
+// Staking reward settings and the state of the reward pool
+const RWDCFG: Symbol = symbol_short!("RWDCFG");
+const RWDPOOL: Symbol = symbol_short!("RWDPOOL");
+
+// Redemption caps per epoch, when the epoch length was last changed and the
+// XLM redeemed by everyone in the current epoch
+const RDMLIMITS: Symbol = symbol_short!("RDMLIMITS");
+const RDMSTART: Symbol = symbol_short!("RDMSTART");
+const RDMGLOBAL: Symbol = symbol_short!("RDMGLOBAL");
+const DEFAULT_REDEMPTION_EPOCH: u64 = 24 * 60 * 60;
+
+// Size above which redemptions vest and how long they vest for
+const VESTING: Symbol = symbol_short!("VESTING");
+const DEFAULT_VESTING_DURATION: u64 = 30 * 24 * 60 * 60;
+
+// Delay before scheduled setting changes can be executed (0 disables the timelock),
+// the queue of scheduled changes and the id the next one gets
+const TLDELAY: Symbol = symbol_short!("TLDELAY");
+const TLQUEUE: Symbol = symbol_short!("TLQUEUE");
+const TLNEXT: Symbol = symbol_short!("TLNEXT");
+
+// How long unstaked tokens stay locked, in seconds
+const UNBOND: Symbol = symbol_short!("UNBOND");
+const DEFAULT_UNBONDING_PERIOD: u64 = 7 * 24 * 60 * 60;
+
+// Share of interaction karma credited to the content's author, in basis points
+const AUTHSHR: Symbol = symbol_short!("AUTHSHR");
+
+// Karma half-life in seconds (0 disables decay) and every change to it as
+// (timestamp, half-life) pairs, oldest first
+const DECAY: Symbol = symbol_short!("DECAY");
+const DECAYH: Symbol = symbol_short!("DECAYH");
+
+// Legacy instance-storage maps, only read by `migrate_legacy`
+pub(crate) const LEGACY_USERS: Symbol = symbol_short!("USERS");
+pub(crate) const LEGACY_STAKES: Symbol = symbol_short!("STAKES");
+pub(crate) const LEGACY_ACTIVITY: Symbol = symbol_short!("ACTIVITY");
+
+// Legacy conversion rate: karma per stroop, replaced by `KarmaRate` under `KARMARATE`
+pub(crate) const LEGACY_RATE: Symbol = symbol_short!("KARMART");
+
+// User record layout used by the legacy `USERS` map
+#[contracttype]
+#[derive(Clone)]
+pub struct LegacyUserData {
+    pub karma_points: i32,
+    pub registered: bool,
+}
+
+// Activity record layout used by the legacy `ACTIVITY` map
+#[contracttype]
+#[derive(Clone)]
+pub struct LegacyActivityRecord {
+    pub activity_type: ActivityType,
+    pub karma_change: i32,
+    pub timestamp: u64,
+}
+
+// Per-user storage keys (persistent storage)
+#[contracttype]
+#[derive(Clone)]
+pub enum DataKey {
+    User(Address),
+    Stake(Address),
+    Unbonding(Address),
+    Vesting(Address),
+    StakerRewards(Address),
+    Activity(Address),
+    Role(Role, Address),
+    Treasury(Address),
+    StakeToken(Address),
+    DelistedToken(Address), // settings and reward accumulator of a token when it was delisted
+    Nonces(Address, u64), // bitmap of used attestation nonces `64 * word..64 * (word + 1)`
+    ActivityKind(Symbol),
+    Leaderboard,
+    Cooldown(Symbol),
+    RateExempt(Address),
+    Seen(Address, Symbol, BytesN<32>), // (user, activity kind, content id)
+    LastActivity(Address, Symbol), // temporary
+    KarmaGained(Address),          // temporary
+    Redeemed(Address),             // temporary
+}
+
+/// Extend the TTL of the contract instance (settings and running totals)
+pub fn extend_instance(e: &Env) {
+    e.storage()
+        .instance()
+        .extend_ttl(INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT);
+}
+
+/// Extend the TTL of every persistent entry that belongs to `user`
+pub fn extend_user(e: &Env, user: &Address) {
+    let persistent = e.storage().persistent();
+    for key in [
+        DataKey::User(user.clone()),
+        DataKey::Stake(user.clone()),
+        DataKey::Unbonding(user.clone()),
+        DataKey::Vesting(user.clone()),
+        DataKey::StakerRewards(user.clone()),
+        DataKey::Activity(user.clone()),
+    ] {
+        if persistent.has(&key) {
+            persistent.extend_ttl(&key, USER_LIFETIME_THRESHOLD, USER_BUMP_AMOUNT);
+        }
+    }
+}
+
+pub fn has_user(e: &Env, user: &Address) -> bool {
+    e.storage().persistent().has(&DataKey::User(user.clone()))
+}
+
+pub fn read_user(e: &Env, user: &Address) -> Option<UserData> {
+    e.storage().persistent().get(&DataKey::User(user.clone()))
+}
+
+/// Read a user with decay applied up to the current ledger time
+pub fn read_decayed_user(e: &Env, user: &Address) -> Option<UserData> {
+    let mut user_data = read_user(e, user)?;
+    user_data.karma_points = decayed(e, user_data.karma_points, user_data.last_update);
+    user_data.last_update = e.ledger().timestamp();
+    Some(user_data)
+}
+
+pub fn write_user(e: &Env, user: &Address, user_data: &UserData) {
+    e.storage().persistent().set(&DataKey::User(user.clone()), user_data);
+}
+
+/// A user's stake per token
+pub fn read_stakes(e: &Env, user: &Address) -> Map<Address, i128> {
+    e.storage()
+        .persistent()
+        .get(&DataKey::Stake(user.clone()))
+        .unwrap_or_else(|| Map::new(e))
+}
+
+pub fn write_stakes(e: &Env, user: &Address, stakes: &Map<Address, i128>) {
+    e.storage().persistent().set(&DataKey::Stake(user.clone()), stakes);
+}
+
+pub fn read_stake(e: &Env, user: &Address, token: &Address) -> i128 {
+    read_stakes(e, user).get(token.clone()).unwrap_or(0)
+}
+
+pub fn write_stake(e: &Env, user: &Address, token: &Address, amount: i128) {
+    let mut stakes = read_stakes(e, user);
+    if amount == 0 {
+        stakes.remove(token.clone());
+    } else {
+        stakes.set(token.clone(), amount);
+    }
+    write_stakes(e, user, &stakes);
+}
+
+pub fn read_unbonding(e: &Env, user: &Address) -> Vec<UnbondingEntry> {
+    e.storage()
+        .persistent()
+        .get(&DataKey::Unbonding(user.clone()))
+        .unwrap_or_else(|| Vec::new(e))
+}
+
+pub fn write_unbonding(e: &Env, user: &Address, pending: &Vec<UnbondingEntry>) {
+    let key = DataKey::Unbonding(user.clone());
+    if pending.is_empty() {
+        e.storage().persistent().remove(&key);
+    } else {
+        e.storage().persistent().set(&key, pending);
+    }
+}
+
+pub fn read_vesting(e: &Env, user: &Address) -> Vec<VestingEntry> {
+    e.storage()
+        .persistent()
+        .get(&DataKey::Vesting(user.clone()))
+        .unwrap_or_else(|| Vec::new(e))
+}
+
+pub fn write_vesting(e: &Env, user: &Address, entries: &Vec<VestingEntry>) {
+    let key = DataKey::Vesting(user.clone());
+    if entries.is_empty() {
+        e.storage().persistent().remove(&key);
+    } else {
+        e.storage().persistent().set(&key, entries);
+    }
+}
+
+pub fn read_staker_rewards(e: &Env, user: &Address) -> StakerRewards {
+    e.storage()
+        .persistent()
+        .get(&DataKey::StakerRewards(user.clone()))
+        .unwrap_or_default()
+}
+
+pub fn write_staker_rewards(e: &Env, user: &Address, staker: &StakerRewards) {
+    e.storage().persistent().set(&DataKey::StakerRewards(user.clone()), staker);
+}
+
+pub fn read_activities(e: &Env, user: &Address) -> Vec<ActivityRecord> {
+    e.storage()
+        .persistent()
+        .get(&DataKey::Activity(user.clone()))
+        .unwrap_or_else(|| Vec::new(e))
+}
+
+pub fn write_activities(e: &Env, user: &Address, activities: &Vec<ActivityRecord>) {
+    e.storage().persistent().set(&DataKey::Activity(user.clone()), activities);
+}
+
+/// Add a record to the front of a user's history, keeping the newest MAX_ACTIVITY_HISTORY
+pub fn push_activity(e: &Env, user: &Address, activity_record: ActivityRecord) {
+    let mut activities = read_activities(e, user);
+    activities.push_front(activity_record);
+    while activities.len() > MAX_ACTIVITY_HISTORY {
+        activities.pop_back();
+    }
+    write_activities(e, user, &activities);
+}
+
+pub fn is_nonce_used(e: &Env, user: &Address, nonce: u64) -> bool {
+    let word: u64 = e.storage().persistent().get(&DataKey::Nonces(user.clone(), nonce / 64)).unwrap_or(0);
+    word & (1 << (nonce % 64)) != 0
+}
+
+/// Mark an attestation nonce as used. Bitmaps are only bumped when written;
+/// an archived one is restored on its next read, so used nonces stay used.
+pub fn mark_nonce_used(e: &Env, user: &Address, nonce: u64) {
+    let key = DataKey::Nonces(user.clone(), nonce / 64);
+    let word: u64 = e.storage().persistent().get(&key).unwrap_or(0);
+    e.storage().persistent().set(&key, &(word | (1 << (nonce % 64))));
+    e.storage().persistent().extend_ttl(&key, USER_LIFETIME_THRESHOLD, USER_BUMP_AMOUNT);
+}
+
+pub fn has_role(e: &Env, role: Role, account: &Address) -> bool {
+    let key = DataKey::Role(role, account.clone());
+    let granted = e.storage().persistent().get(&key).unwrap_or(false);
+    if granted {
+        e.storage()
+            .persistent()
+            .extend_ttl(&key, USER_LIFETIME_THRESHOLD, USER_BUMP_AMOUNT);
+    }
+    granted
+}
+
+pub fn set_role(e: &Env, role: Role, account: &Address, granted: bool) {
+    let key = DataKey::Role(role, account.clone());
+    if granted {
+        e.storage().persistent().set(&key, &true);
+        e.storage()
+            .persistent()
+            .extend_ttl(&key, USER_LIFETIME_THRESHOLD, USER_BUMP_AMOUNT);
+    } else {
+        e.storage().persistent().remove(&key);
+    }
+}
+
+pub fn read_treasury(e: &Env, token: &Address) -> i128 {
+    let key = DataKey::Treasury(token.clone());
+    let balance: Option<i128> = e.storage().persistent().get(&key);
+    if balance.is_some() {
+        e.storage()
+            .persistent()
+            .extend_ttl(&key, USER_LIFETIME_THRESHOLD, USER_BUMP_AMOUNT);
+    }
+    balance.unwrap_or(0)
+}
+
+pub fn write_treasury(e: &Env, token: &Address, balance: i128) {
+    let key = DataKey::Treasury(token.clone());
+    e.storage().persistent().set(&key, &balance);
+    e.storage()
+        .persistent()
+        .extend_ttl(&key, USER_LIFETIME_THRESHOLD, USER_BUMP_AMOUNT);
+}
+
+pub fn read_stake_asset(e: &Env, token: &Address) -> Option<StakeAsset> {
+    let key = DataKey::StakeToken(token.clone());
+    let asset: Option<StakeAsset> = e.storage().persistent().get(&key);
+    if asset.is_some() {
+        e.storage()
+            .persistent()
+            .extend_ttl(&key, USER_LIFETIME_THRESHOLD, USER_BUMP_AMOUNT);
+    }
+    asset
+}
+
+/// Accept `token` for staking with the given settings, or stop accepting it with `None`
+pub fn write_stake_asset(e: &Env, token: &Address, asset: Option<&StakeAsset>) {
+    let key = DataKey::StakeToken(token.clone());
+    if let Some(asset) = asset {
+        e.storage().persistent().set(&key, asset);
+        e.storage()
+            .persistent()
+            .extend_ttl(&key, USER_LIFETIME_THRESHOLD, USER_BUMP_AMOUNT);
+    } else {
+        e.storage().persistent().remove(&key);
+    }
+}
+
+pub fn read_delisted_asset(e: &Env, token: &Address) -> Option<(StakeAsset, i128)> {
+    let key = DataKey::DelistedToken(token.clone());
+    let delisted: Option<(StakeAsset, i128)> = e.storage().persistent().get(&key);
+    if delisted.is_some() {
+        e.storage()
+            .persistent()
+            .extend_ttl(&key, USER_LIFETIME_THRESHOLD, USER_BUMP_AMOUNT);
+    }
+    delisted
+}
+
+/// Remember the settings `token` had when it was delisted at reward accumulator
+/// value `per_share`, or forget them with `None` when it is accepted again
+pub fn write_delisted_asset(e: &Env, token: &Address, delisted: Option<(&StakeAsset, i128)>) {
+    let key = DataKey::DelistedToken(token.clone());
+    if let Some((asset, per_share)) = delisted {
+        e.storage().persistent().set(&key, &(asset.clone(), per_share));
+        e.storage()
+            .persistent()
+            .extend_ttl(&key, USER_LIFETIME_THRESHOLD, USER_BUMP_AMOUNT);
+    } else {
+        e.storage().persistent().remove(&key);
+    }
+}
+
+pub fn read_activity_kind(e: &Env, kind: &Symbol) -> Option<ActivityKind> {
+    let key = DataKey::ActivityKind(kind.clone());
+    let activity_kind: Option<ActivityKind> = e.storage().persistent().get(&key);
+    if activity_kind.is_some() {
+        e.storage()
+            .persistent()
+            .extend_ttl(&key, USER_LIFETIME_THRESHOLD, USER_BUMP_AMOUNT);
+    }
+    activity_kind
+}
+
+pub fn write_activity_kind(e: &Env, kind: &Symbol, activity_kind: &ActivityKind) {
+    let key = DataKey::ActivityKind(kind.clone());
+    e.storage().persistent().set(&key, activity_kind);
+    e.storage()
+        .persistent()
+        .extend_ttl(&key, USER_LIFETIME_THRESHOLD, USER_BUMP_AMOUNT);
+}
+
+pub fn read_cooldown(e: &Env, kind: &Symbol) -> u64 {
+    let key = DataKey::Cooldown(kind.clone());
+    let cooldown: Option<u64> = e.storage().persistent().get(&key);
+    if cooldown.is_some() {
+        e.storage()
+            .persistent()
+            .extend_ttl(&key, USER_LIFETIME_THRESHOLD, USER_BUMP_AMOUNT);
+    }
+    cooldown.unwrap_or(0)
+}
+
+pub fn write_cooldown(e: &Env, kind: &Symbol, seconds: u64) {
+    let key = DataKey::Cooldown(kind.clone());
+    if seconds == 0 {
+        e.storage().persistent().remove(&key);
+    } else {
+        e.storage().persistent().set(&key, &seconds);
+        e.storage()
+            .persistent()
+            .extend_ttl(&key, USER_LIFETIME_THRESHOLD, USER_BUMP_AMOUNT);
+    }
+}
+
+pub fn read_daily_cap(e: &Env) -> i32 {
+    e.storage().instance().get(&DAILYCAP).unwrap_or(0)
+}
+
+pub fn write_daily_cap(e: &Env, cap: i32) {
+    e.storage().instance().set(&DAILYCAP, &cap);
+}
+
+pub fn read_multiplier_schedule(e: &Env) -> Option<MultiplierSchedule> {
+    e.storage().instance().get(&MULTSCHED)
+}
+
+pub fn write_multiplier_schedule(e: &Env, schedule: &MultiplierSchedule) {
+    e.storage().instance().set(&MULTSCHED, schedule);
+}
+
+pub fn read_slash_rate(e: &Env) -> Option<u32> {
+    e.storage().instance().get(&SLASHRT)
+}
+
+pub fn write_slash_rate(e: &Env, rate_bps: u32) {
+    e.storage().instance().set(&SLASHRT, &rate_bps);
+}
+
+pub fn read_reward_config(e: &Env) -> RewardConfig {
+    e.storage().instance().get(&RWDCFG).unwrap_or(RewardConfig {
+        rate: 0,
+        max_karma_boost: 0,
+        boost_karma: 0,
+    })
+}
+
+pub fn write_reward_config(e: &Env, config: &RewardConfig) {
+    e.storage().instance().set(&RWDCFG, config);
+}
+
+pub fn read_reward_pool(e: &Env) -> RewardPool {
+    e.storage().instance().get(&RWDPOOL).unwrap_or_default()
+}
+
+pub fn write_reward_pool(e: &Env, pool: &RewardPool) {
+    e.storage().instance().set(&RWDPOOL, pool);
+}
+
+pub fn read_redemption_limits(e: &Env) -> RedemptionLimits {
+    e.storage().instance().get(&RDMLIMITS).unwrap_or(RedemptionLimits {
+        epoch: DEFAULT_REDEMPTION_EPOCH,
+        user_cap: 0,
+        global_cap: 0,
+    })
+}
+
+pub fn write_redemption_limits(e: &Env, limits: &RedemptionLimits) {
+    e.storage().instance().set(&RDMLIMITS, limits);
+}
+
+/// Ledger timestamp epochs are counted from
+pub fn read_redemption_start(e: &Env) -> u64 {
+    e.storage().instance().get(&RDMSTART).unwrap_or(0)
+}
+
+pub fn write_redemption_start(e: &Env, start: u64) {
+    e.storage().instance().set(&RDMSTART, &start);
+}
+
+pub fn read_global_redeemed(e: &Env) -> Option<EpochUsage> {
+    e.storage().instance().get(&RDMGLOBAL)
+}
+
+pub fn write_global_redeemed(e: &Env, usage: &EpochUsage) {
+    e.storage().instance().set(&RDMGLOBAL, usage);
+}
+
+pub fn read_user_redeemed(e: &Env, user: &Address) -> Option<EpochUsage> {
+    e.storage().temporary().get(&DataKey::Redeemed(user.clone()))
+}
+
+/// Remember what `user` redeemed this epoch for at least `seconds`
+pub fn write_user_redeemed(e: &Env, user: &Address, usage: &EpochUsage, seconds: u64) {
+    let key = DataKey::Redeemed(user.clone());
+    e.storage().temporary().set(&key, usage);
+    let ttl = ledgers_covering(e, seconds);
+    e.storage().temporary().extend_ttl(&key, ttl, ttl);
+}
+
+pub fn read_vesting_config(e: &Env) -> VestingConfig {
+    e.storage().instance().get(&VESTING).unwrap_or(VestingConfig {
+        threshold: 0,
+        duration: DEFAULT_VESTING_DURATION,
+    })
+}
+
+pub fn write_vesting_config(e: &Env, config: &VestingConfig) {
+    e.storage().instance().set(&VESTING, config);
+}
+
+pub fn read_timelock_delay(e: &Env) -> u64 {
+    e.storage().instance().get(&TLDELAY).unwrap_or(0)
+}
+
+pub fn write_timelock_delay(e: &Env, seconds: u64) {
+    e.storage().instance().set(&TLDELAY, &seconds);
+}
+
+pub fn read_pending_changes(e: &Env) -> Vec<PendingChange> {
+    e.storage().instance().get(&TLQUEUE).unwrap_or_else(|| Vec::new(e))
+}
+
+pub fn write_pending_changes(e: &Env, queue: &Vec<PendingChange>) {
+    e.storage().instance().set(&TLQUEUE, queue);
+}
+
+/// Hand out the id of the next scheduled change
+pub fn next_change_id(e: &Env) -> u32 {
+    let id: u32 = e.storage().instance().get(&TLNEXT).unwrap_or(0);
+    e.storage().instance().set(&TLNEXT, &(id + 1));
+    id
+}
+
+pub fn read_unbonding_period(e: &Env) -> u64 {
+    e.storage().instance().get(&UNBOND).unwrap_or(DEFAULT_UNBONDING_PERIOD)
+}
+
+pub fn write_unbonding_period(e: &Env, seconds: u64) {
+    e.storage().instance().set(&UNBOND, &seconds);
+}
+
+pub fn read_author_share(e: &Env) -> u32 {
+    e.storage().instance().get(&AUTHSHR).unwrap_or(0)
+}
+
+pub fn write_author_share(e: &Env, share_bps: u32) {
+    e.storage().instance().set(&AUTHSHR, &share_bps);
+}
+
+pub fn is_rate_exempt(e: &Env, user: &Address) -> bool {
+    let key = DataKey::RateExempt(user.clone());
+    let exempt = e.storage().persistent().get(&key).unwrap_or(false);
+    if exempt {
+        e.storage()
+            .persistent()
+            .extend_ttl(&key, USER_LIFETIME_THRESHOLD, USER_BUMP_AMOUNT);
+    }
+    exempt
+}
+
+pub fn set_rate_exempt(e: &Env, user: &Address, exempt: bool) {
+    let key = DataKey::RateExempt(user.clone());
+    if exempt {
+        e.storage().persistent().set(&key, &true);
+        e.storage()
+            .persistent()
+            .extend_ttl(&key, USER_LIFETIME_THRESHOLD, USER_BUMP_AMOUNT);
+    } else {
+        e.storage().persistent().remove(&key);
+    }
+}
+
+pub fn is_seen(e: &Env, user: &Address, kind: &Symbol, content_id: &BytesN<32>) -> bool {
+    e.storage()
+        .persistent()
+        .has(&DataKey::Seen(user.clone(), kind.clone(), content_id.clone()))
+}
+
+/// Remember that `user` recorded `kind` on `content_id`. Markers live as long
+/// as user entries; an archived marker has to be restored before the same
+/// content can be acted on again, so duplicates are still caught.
+pub fn mark_seen(e: &Env, user: &Address, kind: &Symbol, content_id: &BytesN<32>) {
+    let key = DataKey::Seen(user.clone(), kind.clone(), content_id.clone());
+    e.storage().persistent().set(&key, &true);
+    e.storage()
+        .persistent()
+        .extend_ttl(&key, USER_LIFETIME_THRESHOLD, USER_BUMP_AMOUNT);
+}
+
+pub fn read_last_activity(e: &Env, user: &Address, kind: &Symbol) -> Option<u64> {
+    e.storage()
+        .temporary()
+        .get(&DataKey::LastActivity(user.clone(), kind.clone()))
+}
+
+/// Remember when `user` last performed `kind` for at least `seconds`
+pub fn write_last_activity(e: &Env, user: &Address, kind: &Symbol, timestamp: u64, seconds: u64) {
+    let key = DataKey::LastActivity(user.clone(), kind.clone());
+    e.storage().temporary().set(&key, &timestamp);
+    let ttl = ledgers_covering(e, seconds);
+    e.storage().temporary().extend_ttl(&key, ttl, ttl);
+}
+
+pub fn read_gains(e: &Env, user: &Address) -> Vec<GainBucket> {
+    e.storage()
+        .temporary()
+        .get(&DataKey::KarmaGained(user.clone()))
+        .unwrap_or_else(|| Vec::new(e))
+}
+
+/// Store a user's recent karma gains for at least `seconds`
+pub fn write_gains(e: &Env, user: &Address, buckets: &Vec<GainBucket>, seconds: u64) {
+    let key = DataKey::KarmaGained(user.clone());
+    e.storage().temporary().set(&key, buckets);
+    let ttl = ledgers_covering(e, seconds);
+    e.storage().temporary().extend_ttl(&key, ttl, ttl);
+}
+
+/// Ledgers a temporary entry must live to outlast `seconds` (~5s per ledger, plus a day of slack)
+fn ledgers_covering(e: &Env, seconds: u64) -> u32 {
+    let ledgers = seconds / 5 + DAY_IN_LEDGERS as u64;
+    ledgers.min(e.storage().max_ttl() as u64) as u32
+}
+
+pub fn read_leaderboard(e: &Env) -> Vec<LeaderboardEntry> {
+    let leaderboard: Option<Vec<LeaderboardEntry>> = e.storage().persistent().get(&DataKey::Leaderboard);
+    if leaderboard.is_some() {
+        e.storage()
+            .persistent()
+            .extend_ttl(&DataKey::Leaderboard, USER_LIFETIME_THRESHOLD, USER_BUMP_AMOUNT);
+    }
+    leaderboard.unwrap_or_else(|| Vec::new(e))
+}
+
+pub fn write_leaderboard(e: &Env, leaderboard: &Vec<LeaderboardEntry>) {
+    e.storage().persistent().set(&DataKey::Leaderboard, leaderboard);
+    e.storage()
+        .persistent()
+        .extend_ttl(&DataKey::Leaderboard, USER_LIFETIME_THRESHOLD, USER_BUMP_AMOUNT);
+}
+
+pub fn read_leaderboard_size(e: &Env) -> u32 {
+    e.storage().instance().get(&LBSIZE).unwrap_or(DEFAULT_LEADERBOARD_SIZE)
+}
+
+pub fn write_leaderboard_size(e: &Env, size: u32) {
+    e.storage().instance().set(&LBSIZE, &size);
+}
+
+pub fn read_half_life(e: &Env) -> u64 {
+    e.storage().instance().get(&DECAY).unwrap_or(0)
+}
+
+pub fn write_half_life(e: &Env, half_life: u64) {
+    let mut changes: Vec<(u64, u64)> = e.storage().instance().get(&DECAYH).unwrap_or(Vec::new(e));
+    changes.push_back((e.ledger().timestamp(), half_life));
+    e.storage().instance().set(&DECAYH, &changes);
+    e.storage().instance().set(&DECAY, &half_life);
+}
+
+/// Decay a karma value last updated at `last_update` to the current ledger time.
+/// Each span decays at the half-life in force during it, so changing the
+/// half-life never applies retroactively nor undoes decay already accrued.
+pub fn decayed(e: &Env, karma: i32, last_update: u64) -> i32 {
+    let changes: Vec<(u64, u64)> = e.storage().instance().get(&DECAYH).unwrap_or(Vec::new(e));
+    let mut karma = karma;
+    let mut from = last_update;
+    let mut half_life = 0;
+    for (since, next) in changes.iter() {
+        if since > from {
+            karma = decay(karma, since - from, half_life);
+            from = since;
+        }
+        half_life = next;
+    }
+    decay(karma, e.ledger().timestamp().saturating_sub(from), half_life)
+}
+
+/// Read the running total with decay applied up to the current ledger time.
+/// Every user decays at the same rate, so decaying the sum matches the sum
+/// of decayed balances (up to rounding).
+pub fn read_total_karma(e: &Env) -> i32 {
+    let total: i32 = e.storage().instance().get(&TOTALK).unwrap_or(0);
+    let updated: u64 = e.storage().instance().get(&TOTALT).unwrap_or(0);
+    decayed(e, total, updated)
+}
+
+/// Apply a karma delta to the running total
+pub fn add_total_karma(e: &Env, delta: i32) {
+    let total = read_total_karma(e) + delta;
+    e.storage().instance().set(&TOTALK, &total);
+    e.storage().instance().set(&TOTALT, &e.ledger().timestamp());
+}
+
+/// Move up to `limit` entries from the legacy instance maps into keyed
+/// persistent storage. Returns how many legacy entries are left.
+pub fn migrate_legacy(e: &Env, limit: u32) -> u32 {
+    let instance = e.storage().instance();
+    let mut users: Map<Address, LegacyUserData> = instance.get(&LEGACY_USERS).unwrap_or_else(|| Map::new(e));
+    let mut stakes: Map<Address, i128> = instance.get(&LEGACY_STAKES).unwrap_or_else(|| Map::new(e));
+    let mut activities: Map<Address, Vec<LegacyActivityRecord>> =
+        instance.get(&LEGACY_ACTIVITY).unwrap_or_else(|| Map::new(e));
+
+    // The old rate is a single entry and moves regardless of `limit`. A rate
+    // set since the upgrade is newer and wins over it.
+    if let Some(rate) = instance.get::<_, i128>(&LEGACY_RATE) {
+        if !instance.has(&crate::KARMARATE) {
+            let karma = rate.clamp(1, u32::MAX as i128) as u32;
+            instance.set(&crate::KARMARATE, &crate::KarmaRate { karma, stroops: 1 });
+        }
+        instance.remove(&LEGACY_RATE);
+    }
+
+    let mut budget = limit;
+
+    // Legacy stakes were all made in the XLM token
+    let xlm_token: Address = instance.get(&crate::KARMATOK).unwrap();
+
+    for user in users.keys() {
+        if budget == 0 {
+            break;
+        }
+        let legacy = users.get(user.clone()).unwrap();
+        KarmaEngineContract::sync_reward_shares(e, &user);
+        // Users that re-registered before the migration keep their new entry
+        let mut user_data = read_decayed_user(e, &user).unwrap_or(UserData {
+            karma_points: 0,
+            registered: true,
+            last_update: e.ledger().timestamp(),
+        });
+        user_data.karma_points += legacy.karma_points;
+        write_user(e, &user, &user_data);
+        extend_user(e, &user);
+        add_total_karma(e, legacy.karma_points);
+        leaderboard::update(e, &user, &user_data);
+        KarmaEngineContract::sync_reward_shares(e, &user);
+        users.remove(user);
+        budget -= 1;
+    }
+
+    for user in stakes.keys() {
+        if budget == 0 {
+            break;
+        }
+        let amount = stakes.get(user.clone()).unwrap();
+        KarmaEngineContract::sync_reward_shares(e, &user);
+        write_stake(e, &user, &xlm_token, read_stake(e, &user, &xlm_token) + amount);
+        extend_user(e, &user);
+        KarmaEngineContract::sync_reward_shares(e, &user);
+        stakes.remove(user);
+        budget -= 1;
+    }
+
+    for user in activities.keys() {
+        if budget == 0 {
+            break;
+        }
+        let legacy = activities.get(user.clone()).unwrap();
+        // Newer records stay in front, legacy ones are appended after them.
+        // Legacy records predate content ids and get an all-zero one.
+        let mut merged = read_activities(e, &user);
+        for record in legacy.iter() {
+            merged.push_back(ActivityRecord {
+                activity_type: record.activity_type,
+                content_id: BytesN::from_array(e, &[0; 32]),
+                author: None,
+                karma_change: record.karma_change,
+                timestamp: record.timestamp,
+            });
+        }
+        while merged.len() > MAX_ACTIVITY_HISTORY {
+            merged.pop_back();
+        }
+        write_activities(e, &user, &merged);
+        extend_user(e, &user);
+        activities.remove(user);
+        budget -= 1;
+    }
+
+    store_or_remove(e, &LEGACY_USERS, &users);
+    store_or_remove(e, &LEGACY_STAKES, &stakes);
+    store_or_remove(e, &LEGACY_ACTIVITY, &activities);
+
+    users.len() + stakes.len() + activities.len()
+}
+
+fn store_or_remove<V>(e: &Env, key: &Symbol, map: &Map<Address, V>)
+where
+    V: soroban_sdk::IntoVal<Env, soroban_sdk::Val> + soroban_sdk::TryFromVal<Env, soroban_sdk::Val>,
+{
+    if map.is_empty() {
+        e.storage().instance().remove(key);
+    } else {
+        e.storage().instance().set(key, map);
+    }
+}
@@ -0,0 +1,36 @@
+use soroban_sdk::{Env, Vec};
+
+use crate::storage;
+use crate::{KarmaError, ParamChange, PendingChange};
+
+/// Queue `change` to become executable once the current delay has passed
+pub fn schedule(e: &Env, change: ParamChange) -> PendingChange {
+    let id = storage::next_change_id(e);
+    let eta = e.ledger().timestamp() + storage::read_timelock_delay(e);
+    let pending = PendingChange { id, change, eta };
+
+    let mut queue = storage::read_pending_changes(e);
+    queue.push_back(pending.clone());
+    storage::write_pending_changes(e, &queue);
+
+    pending
+}
+
+/// Look up a queued change
+pub fn get(e: &Env, id: u32) -> Result<PendingChange, KarmaError> {
+    storage::read_pending_changes(e)
+        .iter()
+        .find(|pending| pending.id == id)
+        .ok_or(KarmaError::UnknownChange)
+}
+
+/// Drop a queued change
+pub fn remove(e: &Env, id: u32) {
+    let mut queue = Vec::new(e);
+    for pending in storage::read_pending_changes(e).iter() {
+        if pending.id != id {
+            queue.push_back(pending);
+        }
+    }
+    storage::write_pending_changes(e, &queue);
+}
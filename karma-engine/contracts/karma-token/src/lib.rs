@@ -0,0 +1,264 @@
+#![no_std]
+// Events use the SEP-41 topic layout through `Events::publish`, like the engine
+#![allow(deprecated)]
+use soroban_sdk::{contract, contracterror, contractimpl, contractmeta, contracttype, panic_with_error, symbol_short, Address, Env, MuxedAddress, String, Symbol};
+use soroban_sdk::token::TokenInterface;
+
+contractmeta!(
+    key = "Description",
+    val = "KARMA - SEP-41 token minted by the Karma Engine for redeemed karma"
+);
+
+// TTL policy, in ledgers (~5s each)
+const DAY_IN_LEDGERS: u32 = 17280;
+const INSTANCE_BUMP_AMOUNT: u32 = 30 * DAY_IN_LEDGERS;
+const INSTANCE_LIFETIME_THRESHOLD: u32 = INSTANCE_BUMP_AMOUNT - DAY_IN_LEDGERS;
+const BALANCE_BUMP_AMOUNT: u32 = 90 * DAY_IN_LEDGERS;
+const BALANCE_LIFETIME_THRESHOLD: u32 = BALANCE_BUMP_AMOUNT - DAY_IN_LEDGERS;
+
+// Storage keys
+const ADMIN: Symbol = symbol_short!("ADMIN"); // Mint authority, normally the Karma Engine contract
+const DECIMALS: Symbol = symbol_short!("DECIMALS");
+const NAME: Symbol = symbol_short!("NAME");
+const SYMBOL: Symbol = symbol_short!("SYMBOL");
+
+#[contracterror]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, PartialOrd, Ord)]
+#[repr(u32)]
+pub enum TokenError {
+    AlreadyInitialized = 1,
+    InsufficientBalance = 2,
+    InsufficientAllowance = 3,
+    InvalidAmount = 4,
+    InvalidExpiration = 5,
+}
+
+#[contracttype]
+#[derive(Clone)]
+pub struct AllowanceKey {
+    pub from: Address,
+    pub spender: Address,
+}
+
+// Approved amount and the last ledger it can be spent in
+#[contracttype]
+#[derive(Clone)]
+pub struct AllowanceValue {
+    pub amount: i128,
+    pub expiration_ledger: u32,
+}
+
+#[contracttype]
+#[derive(Clone)]
+pub enum DataKey {
+    Balance(Address),
+    Allowance(AllowanceKey), // temporary
+}
+
+#[contract]
+pub struct KarmaToken;
+
+#[contractimpl]
+impl KarmaToken {
+    /// Initialize the token with its mint authority and metadata
+    pub fn initialize(e: Env, admin: Address, decimal: u32, name: String, symbol: String) {
+        if e.storage().instance().has(&ADMIN) {
+            panic_with_error!(&e, TokenError::AlreadyInitialized);
+        }
+        e.storage().instance().set(&ADMIN, &admin);
+        e.storage().instance().set(&DECIMALS, &decimal);
+        e.storage().instance().set(&NAME, &name);
+        e.storage().instance().set(&SYMBOL, &symbol);
+        extend_instance(&e);
+    }
+
+    /// Create `amount` new tokens for `to` (admin only)
+    pub fn mint(e: Env, to: Address, amount: i128) {
+        check_amount(&e, amount);
+        let admin = Self::admin(e.clone());
+        admin.require_auth();
+        extend_instance(&e);
+
+        write_balance(&e, &to, read_balance(&e, &to) + amount);
+
+        e.events().publish((symbol_short!("mint"), to), amount);
+    }
+
+    /// Hand the mint authority to another address (admin only)
+    pub fn set_admin(e: Env, new_admin: Address) {
+        let admin = Self::admin(e.clone());
+        admin.require_auth();
+        extend_instance(&e);
+
+        e.storage().instance().set(&ADMIN, &new_admin);
+
+        e.events().publish((symbol_short!("set_admin"), admin), new_admin);
+    }
+
+    /// Get the mint authority
+    pub fn admin(e: Env) -> Address {
+        e.storage().instance().get(&ADMIN).unwrap()
+    }
+}
+
+#[contractimpl]
+impl TokenInterface for KarmaToken {
+    fn allowance(e: Env, from: Address, spender: Address) -> i128 {
+        read_allowance(&e, &from, &spender).amount
+    }
+
+    fn approve(e: Env, from: Address, spender: Address, amount: i128, expiration_ledger: u32) {
+        from.require_auth();
+        if amount < 0 {
+            panic_with_error!(&e, TokenError::InvalidAmount);
+        }
+        extend_instance(&e);
+
+        write_allowance(&e, &from, &spender, amount, expiration_ledger);
+
+        e.events().publish((symbol_short!("approve"), from, spender), (amount, expiration_ledger));
+    }
+
+    fn balance(e: Env, id: Address) -> i128 {
+        read_balance(&e, &id)
+    }
+
+    fn transfer(e: Env, from: Address, to: MuxedAddress, amount: i128) {
+        from.require_auth();
+        check_amount(&e, amount);
+        extend_instance(&e);
+
+        let to_address = to.address();
+        spend_balance(&e, &from, amount);
+        write_balance(&e, &to_address, read_balance(&e, &to_address) + amount);
+
+        match to.id() {
+            Some(to_muxed_id) => e.events().publish((symbol_short!("transfer"), from, to_address), (to_muxed_id, amount)),
+            None => e.events().publish((symbol_short!("transfer"), from, to_address), amount),
+        }
+    }
+
+    fn transfer_from(e: Env, spender: Address, from: Address, to: Address, amount: i128) {
+        spender.require_auth();
+        check_amount(&e, amount);
+        extend_instance(&e);
+
+        spend_allowance(&e, &from, &spender, amount);
+        spend_balance(&e, &from, amount);
+        write_balance(&e, &to, read_balance(&e, &to) + amount);
+
+        e.events().publish((symbol_short!("transfer"), from, to), amount);
+    }
+
+    fn burn(e: Env, from: Address, amount: i128) {
+        from.require_auth();
+        check_amount(&e, amount);
+        extend_instance(&e);
+
+        spend_balance(&e, &from, amount);
+
+        e.events().publish((symbol_short!("burn"), from), amount);
+    }
+
+    fn burn_from(e: Env, spender: Address, from: Address, amount: i128) {
+        spender.require_auth();
+        check_amount(&e, amount);
+        extend_instance(&e);
+
+        spend_allowance(&e, &from, &spender, amount);
+        spend_balance(&e, &from, amount);
+
+        e.events().publish((symbol_short!("burn"), from), amount);
+    }
+
+    fn decimals(e: Env) -> u32 {
+        e.storage().instance().get(&DECIMALS).unwrap()
+    }
+
+    fn name(e: Env) -> String {
+        e.storage().instance().get(&NAME).unwrap()
+    }
+
+    fn symbol(e: Env) -> String {
+        e.storage().instance().get(&SYMBOL).unwrap()
+    }
+}
+
+fn extend_instance(e: &Env) {
+    e.storage()
+        .instance()
+        .extend_ttl(INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT);
+}
+
+fn check_amount(e: &Env, amount: i128) {
+    if amount < 0 {
+        panic_with_error!(e, TokenError::InvalidAmount);
+    }
+}
+
+fn read_balance(e: &Env, id: &Address) -> i128 {
+    let key = DataKey::Balance(id.clone());
+    match e.storage().persistent().get::<_, i128>(&key) {
+        Some(balance) => {
+            e.storage()
+                .persistent()
+                .extend_ttl(&key, BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT);
+            balance
+        }
+        None => 0,
+    }
+}
+
+fn write_balance(e: &Env, id: &Address, balance: i128) {
+    let key = DataKey::Balance(id.clone());
+    e.storage().persistent().set(&key, &balance);
+    e.storage()
+        .persistent()
+        .extend_ttl(&key, BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT);
+}
+
+fn spend_balance(e: &Env, id: &Address, amount: i128) {
+    let balance = read_balance(e, id);
+    if balance < amount {
+        panic_with_error!(e, TokenError::InsufficientBalance);
+    }
+    write_balance(e, id, balance - amount);
+}
+
+/// Allowance of `spender` over `from`'s tokens, 0 once it has expired
+fn read_allowance(e: &Env, from: &Address, spender: &Address) -> AllowanceValue {
+    let key = DataKey::Allowance(AllowanceKey { from: from.clone(), spender: spender.clone() });
+    match e.storage().temporary().get::<_, AllowanceValue>(&key) {
+        Some(allowance) if allowance.expiration_ledger >= e.ledger().sequence() => allowance,
+        Some(allowance) => AllowanceValue { amount: 0, expiration_ledger: allowance.expiration_ledger },
+        None => AllowanceValue { amount: 0, expiration_ledger: 0 },
+    }
+}
+
+/// Store an allowance that lives until its expiration ledger
+fn write_allowance(e: &Env, from: &Address, spender: &Address, amount: i128, expiration_ledger: u32) {
+    if amount > 0 && expiration_ledger < e.ledger().sequence() {
+        panic_with_error!(e, TokenError::InvalidExpiration);
+    }
+
+    let key = DataKey::Allowance(AllowanceKey { from: from.clone(), spender: spender.clone() });
+    let allowance = AllowanceValue { amount, expiration_ledger };
+    e.storage().temporary().set(&key, &allowance);
+
+    if amount > 0 {
+        let live_for = expiration_ledger - e.ledger().sequence();
+        e.storage().temporary().extend_ttl(&key, live_for, live_for);
+    }
+}
+
+fn spend_allowance(e: &Env, from: &Address, spender: &Address, amount: i128) {
+    let allowance = read_allowance(e, from, spender);
+    if allowance.amount < amount {
+        panic_with_error!(e, TokenError::InsufficientAllowance);
+    }
+    if amount > 0 {
+        write_allowance(e, from, spender, allowance.amount - amount, allowance.expiration_ledger);
+    }
+}
+
+mod test;
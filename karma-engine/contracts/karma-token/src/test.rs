@@ -0,0 +1,139 @@
+#![cfg(test)]
+extern crate std;
+
+use super::{KarmaToken, KarmaTokenClient, TokenError};
+use soroban_sdk::{Env, Address, IntoVal, String, Symbol, symbol_short, vec};
+use soroban_sdk::testutils::{Address as _, AuthorizedFunction, AuthorizedInvocation, Events as _, Ledger};
+use soroban_sdk::token::TokenClient;
+
+fn create_token<'a>(env: &Env, admin: &Address) -> (Address, KarmaTokenClient<'a>) {
+    let token_id = env.register(KarmaToken, ());
+    let client = KarmaTokenClient::new(env, &token_id);
+    client.initialize(admin, &7, &String::from_str(env, "Karma"), &String::from_str(env, "KARMA"));
+    (token_id, client)
+}
+
+#[test]
+fn test_metadata_and_mint() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let admin = Address::generate(&env);
+    let (token_id, client) = create_token(&env, &admin);
+    let token = TokenClient::new(&env, &token_id);
+    
+    assert_eq!(token.decimals(), 7);
+    assert_eq!(token.name(), String::from_str(&env, "Karma"));
+    assert_eq!(token.symbol(), String::from_str(&env, "KARMA"));
+    assert_eq!(client.admin(), admin);
+    
+    // Only the admin can mint
+    let user = Address::generate(&env);
+    client.mint(&user, &1_000);
+    assert_eq!(
+        env.auths(),
+        std::vec![(
+            admin.clone(),
+            AuthorizedInvocation {
+                function: AuthorizedFunction::Contract((token_id.clone(), Symbol::new(&env, "mint"), (user.clone(), 1_000_i128).into_val(&env))),
+                sub_invocations: std::vec![],
+            }
+        )]
+    );
+    assert_eq!(
+        env.events().all(),
+        vec![&env, (token_id.clone(), (symbol_short!("mint"), user.clone()).into_val(&env), 1_000_i128.into_val(&env))]
+    );
+    assert_eq!(token.balance(&user), 1_000);
+    
+    // The mint authority can be handed over
+    let new_admin = Address::generate(&env);
+    client.set_admin(&new_admin);
+    assert_eq!(client.admin(), new_admin);
+    client.mint(&user, &500);
+    assert_eq!(env.auths()[0].0, new_admin);
+    assert_eq!(token.balance(&user), 1_500);
+    
+    // It can only be initialized once
+    assert_eq!(
+        client.try_initialize(&admin, &7, &String::from_str(&env, "Karma"), &String::from_str(&env, "KARMA")),
+        Err(Ok(TokenError::AlreadyInitialized.into()))
+    );
+}
+
+#[test]
+fn test_transfer_and_burn() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let admin = Address::generate(&env);
+    let (token_id, client) = create_token(&env, &admin);
+    let token = TokenClient::new(&env, &token_id);
+    
+    let alice = Address::generate(&env);
+    let bob = Address::generate(&env);
+    client.mint(&alice, &1_000);
+    
+    token.transfer(&alice, &bob, &600);
+    assert_eq!(env.auths()[0].0, alice);
+    assert_eq!(
+        env.events().all(),
+        vec![&env, (token_id.clone(), (symbol_short!("transfer"), alice.clone(), bob.clone()).into_val(&env), 600_i128.into_val(&env))]
+    );
+    assert_eq!(token.balance(&alice), 400);
+    assert_eq!(token.balance(&bob), 600);
+    
+    // Balances cannot go negative, nor can amounts
+    assert_eq!(client.try_transfer(&alice, &bob, &401), Err(Ok(TokenError::InsufficientBalance.into())));
+    assert_eq!(client.try_transfer(&alice, &bob, &-1), Err(Ok(TokenError::InvalidAmount.into())));
+    
+    token.burn(&bob, &100);
+    assert_eq!(
+        env.events().all(),
+        vec![&env, (token_id.clone(), (symbol_short!("burn"), bob.clone()).into_val(&env), 100_i128.into_val(&env))]
+    );
+    assert_eq!(token.balance(&bob), 500);
+}
+
+#[test]
+fn test_allowances() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let admin = Address::generate(&env);
+    let (token_id, client) = create_token(&env, &admin);
+    let token = TokenClient::new(&env, &token_id);
+    
+    let owner = Address::generate(&env);
+    let spender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+    client.mint(&owner, &1_000);
+    
+    let expiration = env.ledger().sequence() + 100;
+    token.approve(&owner, &spender, &300, &expiration);
+    assert_eq!(
+        env.events().all(),
+        vec![&env, (token_id.clone(), (symbol_short!("approve"), owner.clone(), spender.clone()).into_val(&env), (300_i128, expiration).into_val(&env))]
+    );
+    assert_eq!(token.allowance(&owner, &spender), 300);
+    
+    // The spender moves tokens within the allowance, authorizing as themselves
+    token.transfer_from(&spender, &owner, &recipient, &200);
+    assert_eq!(env.auths()[0].0, spender);
+    assert_eq!(token.balance(&recipient), 200);
+    assert_eq!(token.allowance(&owner, &spender), 100);
+    assert_eq!(client.try_transfer_from(&spender, &owner, &recipient, &101), Err(Ok(TokenError::InsufficientAllowance.into())));
+    
+    token.burn_from(&spender, &owner, &100);
+    assert_eq!(token.allowance(&owner, &spender), 0);
+    assert_eq!(token.balance(&owner), 700);
+    
+    // Allowances lapse after their expiration ledger
+    token.approve(&owner, &spender, &300, &expiration);
+    env.ledger().with_mut(|li| li.sequence_number = expiration + 1);
+    assert_eq!(token.allowance(&owner, &spender), 0);
+    assert_eq!(client.try_transfer_from(&spender, &owner, &recipient, &1), Err(Ok(TokenError::InsufficientAllowance.into())));
+    
+    // and cannot be granted in the past
+    assert_eq!(client.try_approve(&owner, &spender, &300, &expiration), Err(Ok(TokenError::InvalidExpiration.into())));
+}